@@ -0,0 +1,298 @@
+//! Runs datdot nodes in-process for integration tests.
+//!
+//! Every node gets a temporary base path and serves no RPC, telemetry or prometheus
+//! endpoints, RPC calls go straight to its handlers. Extrinsics are signed with the
+//! `sp_keyring` dev keys, which are the accounts of the dev and local chain specs.
+
+use datdot_node::{cli::Cli, service};
+use futures::{executor::block_on, future::poll_fn};
+use parity_scale_codec::{Decode, Encode};
+use parking_lot::Mutex;
+use runtime::{
+	dat_verify, Call, Event, Hash, Runtime, SignedExtra, SignedPayload, UncheckedExtrinsic, VERSION,
+};
+use sc_cli::SubstrateCli;
+use sc_service::{AbstractService, RpcSession, TaskType};
+use serde_json::{json, Value};
+use sp_core::{bytes, ed25519, hashing::twox_128, Pair, H256, H512};
+use sp_keyring::AccountKeyring;
+use sp_runtime::generic::Era;
+use std::{
+	collections::HashMap,
+	future::Future,
+	net::TcpListener,
+	pin::Pin,
+	sync::Arc,
+	thread,
+	time::{Duration, Instant},
+};
+use structopt::StructOpt;
+use tempfile::TempDir;
+
+/// Events of the datdot pallet as the runtime emits them
+pub type DatdotEvent = dat_verify::Event<Runtime>;
+
+/// Event records of a block
+pub type EventRecords = Vec<frame_system::EventRecord<Event, Hash>>;
+
+/// A full node running in-process until it is dropped
+pub struct TestNode<S> {
+	service: Arc<Mutex<S>>,
+	// drives the service and its tasks, dropping it shuts the node down
+	_runtime: tokio::runtime::Runtime,
+	nonces: Mutex<HashMap<AccountKeyring, u32>>,
+	genesis_hash: Hash,
+	// removed once the service is dropped
+	_base_path: TempDir,
+}
+
+/// Starts a full node with the arguments `datdot-node` would be started with,
+/// e.g. `&["--dev", "--sealing", "manual"]`.
+pub fn start_node(args: &[&str]) -> TestNode<impl AbstractService> {
+	let base_path = TempDir::new().expect("temporary directory can be created");
+	let base_path_arg = base_path.path().to_string_lossy().into_owned();
+	let mut cli_args = vec![
+		"datdot-node",
+		"--base-path",
+		&base_path_arg,
+		// tests submit the answers offchain workers would
+		"--offchain-worker",
+		"Never",
+		"--no-prometheus",
+		"--no-telemetry",
+	];
+	cli_args.extend_from_slice(args);
+	let cli = Cli::from_iter(cli_args);
+
+	let runtime = tokio::runtime::Runtime::new().expect("tokio runtime can be created");
+	let handle = runtime.handle().clone();
+	let task_executor = Arc::new(
+		move |task: Pin<Box<dyn Future<Output = ()> + Send>>, task_type: TaskType| match task_type {
+			TaskType::Async => {
+				handle.spawn(task);
+			}
+			TaskType::Blocking => {
+				handle.spawn_blocking(move || block_on(task));
+			}
+		},
+	);
+	let mut config = cli
+		.create_configuration(&cli.run, task_executor)
+		.expect("test node arguments are valid");
+	// nodes are only reached through their RPC handlers and the network
+	config.rpc_http = None;
+	config.rpc_ws = None;
+
+	let service = Arc::new(Mutex::new(
+		service::new_full(config, cli.sealing).expect("test node starts"),
+	));
+	let polled = service.clone();
+	runtime.spawn(poll_fn(move |cx| {
+		Pin::new(&mut *polled.lock())
+			.poll(cx)
+			.map(|result| result.expect("test node runs without errors"))
+	}));
+
+	let mut node = TestNode {
+		service,
+		_runtime: runtime,
+		nonces: Mutex::new(HashMap::new()),
+		genesis_hash: Hash::default(),
+		_base_path: base_path,
+	};
+	node.genesis_hash = node.block_hash(0).expect("genesis block exists");
+	node
+}
+
+impl<S: AbstractService> TestNode<S> {
+	/// Calls an RPC method of the node, returning its result or its error
+	pub fn rpc(&self, method: &str, params: Value) -> Result<Value, Value> {
+		let request = json!({
+			"jsonrpc": "2.0",
+			"id": 1,
+			"method": method,
+			"params": params,
+		});
+		let response = self
+			.service
+			.lock()
+			.rpc_query(&RpcSession::default(), &request.to_string());
+		let response = block_on(response).expect("method calls get a response");
+		let mut response: Value = serde_json::from_str(&response).expect("responses are JSON");
+		match response.get_mut("error") {
+			Some(error) => Err(error.take()),
+			None => Ok(response["result"].take()),
+		}
+	}
+
+	/// Signs a datdot call with the dev key of the signer and submits it to the
+	/// transaction pool, nonces are counted from the start of the chain
+	pub fn submit(&self, signer: AccountKeyring, call: dat_verify::Call<Runtime>) -> Hash {
+		let nonce = {
+			let mut nonces = self.nonces.lock();
+			let next = nonces.entry(signer).or_insert(0);
+			*next += 1;
+			*next - 1
+		};
+		let extra: SignedExtra = (
+			frame_system::CheckTxVersion::new(),
+			frame_system::CheckGenesis::new(),
+			frame_system::CheckEra::from(Era::Immortal),
+			frame_system::CheckNonce::from(nonce),
+			frame_system::CheckWeight::new(),
+			pallet_transaction_payment::ChargeTransactionPayment::from(0),
+		);
+		let payload = SignedPayload::from_raw(
+			Call::DatVerify(call),
+			extra,
+			(
+				VERSION.transaction_version,
+				self.genesis_hash,
+				self.genesis_hash,
+				(),
+				(),
+				(),
+			),
+		);
+		let signature = payload.using_encoded(|payload| signer.pair().sign(payload));
+		let (call, extra, _) = payload.deconstruct();
+		let extrinsic =
+			UncheckedExtrinsic::new_signed(call, signer.to_account_id(), signature.into(), extra);
+		let hash = self
+			.rpc(
+				"author_submitExtrinsic",
+				json!([bytes::to_hex(&extrinsic.encode(), false)]),
+			)
+			.expect("extrinsic is accepted by the transaction pool");
+		serde_json::from_value(hash).expect("extrinsic hashes are hashes")
+	}
+
+	/// Seals and finalizes a block with the pending transactions on a `--sealing manual`
+	/// node and returns its events
+	pub fn create_block(&self) -> EventRecords {
+		let created = self
+			.rpc("engine_createBlock", json!([true, true, null]))
+			.expect("manual sealing node creates blocks");
+		let hash = serde_json::from_value(created["hash"].clone()).expect("blocks have a hash");
+		self.events_at(hash)
+	}
+
+	/// Events deposited in a block
+	pub fn events_at(&self, block: Hash) -> EventRecords {
+		let key = [twox_128(b"System"), twox_128(b"Events")].concat();
+		let storage = self
+			.rpc("state_getStorage", json!([bytes::to_hex(&key, false), block]))
+			.expect("storage of imported blocks can be read");
+		match storage.as_str() {
+			Some(encoded) => {
+				let encoded = bytes::from_hex(encoded).expect("storage is hex encoded");
+				Decode::decode(&mut &encoded[..]).expect("events decode as the runtime's")
+			}
+			None => Vec::new(),
+		}
+	}
+
+	/// Hash of the block with the number on the best chain
+	pub fn block_hash(&self, number: u32) -> Option<Hash> {
+		let hash = self
+			.rpc("chain_getBlockHash", json!([number]))
+			.expect("block hashes can be queried");
+		serde_json::from_value(hash).expect("block hashes are hashes or null")
+	}
+
+	/// Number of the best block
+	pub fn best_number(&self) -> u32 {
+		let header = self
+			.rpc("chain_getHeader", json!([]))
+			.expect("the best header can be queried");
+		block_number(&header)
+	}
+
+	/// Number of the last finalized block
+	pub fn finalized_number(&self) -> u32 {
+		let hash = self
+			.rpc("chain_getFinalizedHead", json!([]))
+			.expect("the finalized head can be queried");
+		let header = self
+			.rpc("chain_getHeader", json!([hash]))
+			.expect("the finalized header can be queried");
+		block_number(&header)
+	}
+
+	/// Address other test nodes can reach this node at when it listens on the port
+	pub fn multiaddr(&self, port: u16) -> String {
+		format!(
+			"/ip4/127.0.0.1/tcp/{}/p2p/{}",
+			port,
+			self.service.lock().network().local_peer_id()
+		)
+	}
+}
+
+fn block_number(header: &Value) -> u32 {
+	let number = header["number"].as_str().expect("headers have a hex number");
+	u32::from_str_radix(number.trim_start_matches("0x"), 16).expect("block numbers fit u32")
+}
+
+/// Polls the condition every 100 milliseconds until it holds, panics after the timeout
+pub fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) {
+	let start = Instant::now();
+	while !condition() {
+		assert!(start.elapsed() < timeout, "condition not met within {:?}", timeout);
+		thread::sleep(Duration::from_millis(100));
+	}
+}
+
+/// Distinct localhost ports no one listened on when they were picked, for nodes to listen on.
+/// The ports are released before returning, so start the nodes right away.
+pub fn free_ports(count: usize) -> Vec<u16> {
+	let listeners = (0..count)
+		.map(|_| TcpListener::bind("127.0.0.1:0").expect("the OS assigns a free port"))
+		.collect::<Vec<_>>();
+	listeners
+		.iter()
+		.map(|listener| listener.local_addr().expect("bound listeners have an address").port())
+		.collect()
+}
+
+/// Datdot events among the event records
+pub fn datdot_events(records: &EventRecords) -> Vec<DatdotEvent> {
+	records
+		.iter()
+		.filter_map(|record| match &record.event {
+			Event::dat_verify(event) => Some(event.clone()),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Panics if one of the extrinsics the records were deposited for failed
+pub fn assert_no_failed_extrinsics(records: &EventRecords) {
+	for record in records {
+		if let Event::system(frame_system::RawEvent::ExtrinsicFailed(error, _)) = &record.event {
+			panic!("extrinsic {:?} failed: {:?}", record.phase, error);
+		}
+	}
+}
+
+/// Feed key and signed merkle root of a feed of four chunks under a single root,
+/// distinct for every seed
+pub fn four_chunk_feed(
+	seed: u8,
+) -> (ed25519::Public, dat_verify::TreeHashPayload, H512) {
+	// the pallet keeps the fields of its tree types private, so they are built from their
+	// encoding: hash type, then the roots as (hash, flat tree index, byte length)
+	let payload = (2u8, vec![(H256::repeat_byte(seed), 3u64, 4 * 1024u64)]);
+	(
+		ed25519::Public::from_raw([seed; 32]),
+		Decode::decode(&mut &payload.encode()[..]).expect("payload encodes as a TreeHashPayload"),
+		H512::zero(),
+	)
+}
+
+/// Attestation report of a chunk served within the latency to an attestor in the region
+pub fn served_report(region: u8, latency_ms: u8) -> dat_verify::Report {
+	// built from its encoding like the tree types: location, then latency
+	Decode::decode(&mut &(Some(region), Some(latency_ms)).encode()[..])
+		.expect("report encodes as a Report")
+}
@@ -0,0 +1,55 @@
+//! Runs the three authorities of the local chain as an Aura and GRANDPA network
+//! on localhost and checks datdot state reaches finality on every node.
+
+use datdot_integration_tests::{datdot_events, free_ports, start_node, wait_until};
+use runtime::dat_verify::{Call, RawEvent};
+use sp_keyring::AccountKeyring;
+use std::time::Duration;
+
+#[test]
+fn datdot_events_are_finalized_on_every_node() {
+	let start = |authority: &str, port: u16, bootnodes: &[String]| {
+		let port = port.to_string();
+		let mut args = vec![
+			"--chain",
+			"local",
+			"--sealing",
+			"aura",
+			authority,
+			"--port",
+			&port,
+			"--no-mdns",
+		];
+		for bootnode in bootnodes {
+			args.push("--bootnodes");
+			args.push(bootnode);
+		}
+		start_node(&args)
+	};
+	let ports = free_ports(3);
+	let alice = start("--alice", ports[0], &[]);
+	let bootnode = alice.multiaddr(ports[0]);
+	let bob = start("--bob", ports[1], &[bootnode.clone()]);
+	let charlie = start("--charlie", ports[2], &[bootnode]);
+
+	let dave = AccountKeyring::Dave.to_account_id();
+	alice.submit(AccountKeyring::Dave, Call::new_user());
+
+	for node in &[&alice, &bob, &charlie] {
+		let mut checked = 0;
+		wait_until(Duration::from_secs(120), || {
+			let finalized = node.finalized_number();
+			while checked < finalized {
+				checked += 1;
+				let hash = node.block_hash(checked).expect("finalized blocks are on the best chain");
+				let registered = datdot_events(&node.events_at(hash))
+					.into_iter()
+					.any(|event| matches!(event, RawEvent::NewUser(_, address) if address == dave));
+				if registered {
+					return true;
+				}
+			}
+			false
+		});
+	}
+}
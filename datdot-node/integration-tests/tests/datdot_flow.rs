@@ -0,0 +1,92 @@
+//! Walks a single manual sealing dev node through publishing, hosting, a timed out
+//! proof-of-storage challenge and an attestation.
+
+use datdot_integration_tests::{
+	assert_no_failed_extrinsics, datdot_events, four_chunk_feed, served_report, start_node,
+};
+use runtime::dat_verify::{Call, RawEvent};
+use sp_keyring::AccountKeyring;
+
+#[test]
+fn publish_host_challenge_and_attest() {
+	let node = start_node(&["--dev", "--sealing", "manual"]);
+
+	// Alice and Bob are registered encoders and hosters at genesis,
+	// so publishing a plan creates a contract right away
+	node.submit(
+		AccountKeyring::Alice,
+		Call::publish_feed_and_plan(four_chunk_feed(1), vec![(0, 3)], 0, None),
+	);
+	let records = node.create_block();
+	assert_no_failed_extrinsics(&records);
+	let (contract_id, hoster_id, hoster_address) = datdot_events(&records)
+		.into_iter()
+		.find_map(|event| match event {
+			RawEvent::NewContract(contract_id, _, _, _, _, hoster_id, hoster_address, ranges) => {
+				assert_eq!(ranges, vec![(0, 3)]);
+				Some((contract_id, hoster_id, hoster_address))
+			}
+			_ => None,
+		})
+		.expect("the plan is matched with a genesis encoder and hoster");
+	let hoster = AccountKeyring::from_account_id(&hoster_address).expect("hoster is a dev account");
+
+	node.submit(hoster, Call::hosting_starts(contract_id));
+	let records = node.create_block();
+	assert_no_failed_extrinsics(&records);
+	assert!(datdot_events(&records).contains(&RawEvent::HostingStarted(contract_id, hoster_id)));
+
+	// nobody answers the challenge, it fails once its deadline passed
+	node.submit(
+		AccountKeyring::Alice,
+		Call::request_proof_of_storage_challenge(contract_id),
+	);
+	let records = node.create_block();
+	assert_no_failed_extrinsics(&records);
+	let (challenge_id, deadline) = datdot_events(&records)
+		.into_iter()
+		.find_map(|event| match event {
+			RawEvent::NewProofOfStorageChallenge(challenge_id, contract, hoster, _, _, _, deadline)
+				if contract == contract_id =>
+			{
+				assert_eq!(hoster, hoster_id);
+				Some((challenge_id, deadline))
+			}
+			_ => None,
+		})
+		.expect("challenge is issued to the hoster");
+	while node.best_number() < deadline {
+		let records = node.create_block();
+		assert!(!datdot_events(&records)
+			.contains(&RawEvent::ProofOfStorageFailed(challenge_id, hoster_id)));
+	}
+	let records = node.create_block();
+	assert!(datdot_events(&records).contains(&RawEvent::ProofOfStorageFailed(challenge_id, hoster_id)));
+
+	// the assigned attestor reports every sampled chunk as served
+	node.submit(AccountKeyring::Alice, Call::request_attestation(contract_id));
+	let records = node.create_block();
+	assert_no_failed_extrinsics(&records);
+	let (attestation_id, attestor_address, chunks) = datdot_events(&records)
+		.into_iter()
+		.find_map(|event| match event {
+			RawEvent::NewAttestation(attestation_id, contract, _, attestor_address, _, chunks)
+				if contract == contract_id =>
+			{
+				Some((attestation_id, attestor_address, chunks))
+			}
+			_ => None,
+		})
+		.expect("an attestor is assigned");
+	let attestor =
+		AccountKeyring::from_account_id(&attestor_address).expect("attestor is a dev account");
+	assert!(!chunks.is_empty());
+	assert!(chunks.iter().all(|chunk| *chunk <= 3));
+
+	let reports = chunks.iter().map(|_| served_report(0, 20)).collect();
+	node.submit(attestor, Call::submit_attestation_report(attestation_id, reports));
+	let records = node.create_block();
+	assert_no_failed_extrinsics(&records);
+	assert!(datdot_events(&records)
+		.contains(&RawEvent::AttestationReportConfirmed(attestation_id, hoster_id)));
+}
@@ -1,8 +1,18 @@
 use runtime::{
-	genesis::{account_id_from_seed, dev_genesis, testnet_genesis},
-	GenesisConfig,
+	dat_verify::{Role, StateDump, TreeRoot},
+	genesis::{
+		account_id_from_seed, authority_keys_from_seed, dev_datdot_users, dev_genesis,
+		testnet_genesis, DatdotUser,
+	},
+	AccountId, Balance, BalancesConfig, DatVerify, GenesisConfig, HostersPerPlan, Runtime,
+	Signature,
 };
-use sp_core::sr25519;
+use serde::Deserialize;
+use sc_network::config::MultiaddrWithPeerId;
+use sc_service::ChainType;
+use sp_core::{crypto::Ss58Codec, ed25519, sr25519, Pair};
+use sp_runtime::traits::{IdentifyAccount, Verify};
+use std::{fs::File, path::Path};
 
 // Note this is the URL for the telemetry server
 //const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
@@ -33,6 +43,11 @@ pub fn local_testnet_config() -> ChainSpec {
 		sc_service::ChainType::Local,
 		|| {
 			testnet_genesis(
+				vec![
+					authority_keys_from_seed("Alice"),
+					authority_keys_from_seed("Bob"),
+					authority_keys_from_seed("Charlie"),
+				],
 				account_id_from_seed::<sr25519::Pair>("Alice"),
 				vec![
 					account_id_from_seed::<sr25519::Pair>("Alice"),
@@ -48,6 +63,7 @@ pub fn local_testnet_config() -> ChainSpec {
 					account_id_from_seed::<sr25519::Pair>("Eve//stash"),
 					account_id_from_seed::<sr25519::Pair>("Ferdie//stash"),
 				],
+				dev_datdot_users(&["Alice", "Bob", "Charlie", "Dave", "Eve", "Ferdie"]),
 			)
 		},
 		vec![],
@@ -57,3 +73,241 @@ pub fn local_testnet_config() -> ChainSpec {
 		None,
 	)
 }
+
+/// Build a Development ChainSpec whose genesis restores a state written by
+/// `export-datdot-state`, Alice and Bob are registered on top of it as on the dev chain
+pub fn state_import_config(path: &Path) -> Result<ChainSpec, String> {
+	let file = File::open(path)
+		.map_err(|e| format!("Error opening state file `{}`: {}", path.display(), e))?;
+	let state: StateDump<Runtime> = serde_json::from_reader(file)
+		.map_err(|e| format!("Error parsing state file `{}`: {}", path.display(), e))?;
+	Ok(ChainSpec::from_genesis(
+		"Datdot State Import",
+		"datdot_import",
+		ChainType::Development,
+		move || {
+			let mut genesis = dev_genesis();
+			if let Some(dat_verify) = genesis.dat_verify.as_mut() {
+				dat_verify.state = Some(state.clone());
+			}
+			genesis
+		},
+		vec![],
+		None,
+		None,
+		None,
+		None,
+	))
+}
+
+/// Description of a chain and its datdot participants, read by `build-datdot-spec`.
+///
+/// Keys and accounts are either SS58 addresses or secret URIs like `//Alice`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ParticipantsSpec {
+	name: String,
+	id: String,
+	#[serde(default = "local_chain_type")]
+	chain_type: ChainType,
+	#[serde(default)]
+	boot_nodes: Vec<String>,
+	#[serde(default)]
+	protocol_id: Option<String>,
+	sudo: String,
+	authorities: Vec<AuthorityKeys>,
+	participants: Vec<Participant>,
+	#[serde(default)]
+	feeds: Vec<GenesisFeed>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct AuthorityKeys {
+	aura: String,
+	grandpa: String,
+}
+
+/// An endowed account, registered as datdot user if it has a noise key or roles
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Participant {
+	account: String,
+	#[serde(default = "default_balance")]
+	balance: Balance,
+	#[serde(default)]
+	roles: Vec<Role>,
+	#[serde(default)]
+	noise_key: Option<String>,
+	/// Region the participant hosts in, only used for hosters
+	#[serde(default)]
+	region: u8,
+}
+
+/// A feed published at genesis by one of the participants, with a plan for its ranges
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct GenesisFeed {
+	key: String,
+	/// Signed merkle root of the feed, as `publishFeedAndPlan` takes it
+	merkle_root: TreeRoot,
+	publisher: String,
+	ranges: Vec<(u64, u64)>,
+	#[serde(default)]
+	min_regions: u8,
+}
+
+fn local_chain_type() -> ChainType {
+	ChainType::Local
+}
+
+fn default_balance() -> Balance {
+	1 << 60
+}
+
+/// Genesis state of a participants spec with all keys resolved
+#[derive(Clone)]
+struct ParticipantsGenesis {
+	authorities: Vec<(sr25519::Public, ed25519::Public)>,
+	sudo: AccountId,
+	balances: Vec<(AccountId, Balance)>,
+	users: Vec<DatdotUser>,
+	feeds: Vec<(ed25519::Public, TreeRoot, AccountId, Vec<(u64, u64)>, u8)>,
+}
+
+impl ParticipantsGenesis {
+	fn build(&self) -> GenesisConfig {
+		let mut genesis = testnet_genesis(
+			self.authorities
+				.iter()
+				.map(|(aura, grandpa)| (aura.clone().into(), grandpa.clone().into()))
+				.collect(),
+			self.sudo.clone(),
+			self.balances.iter().map(|(account, _)| account.clone()).collect(),
+			self.users.clone(),
+		);
+		genesis.balances = Some(BalancesConfig {
+			balances: self.balances.clone(),
+		});
+		if let Some(dat_verify) = genesis.dat_verify.as_mut() {
+			dat_verify.feeds = self.feeds.clone();
+		}
+		genesis
+	}
+}
+
+/// Parses an SS58 encoded public key or derives it from a secret URI
+fn public_key<P: Pair>(key: &str) -> Result<P::Public, String>
+where
+	P::Public: Ss58Codec,
+{
+	P::Public::from_ss58check(key)
+		.or_else(|_| P::from_string(key, None).map(|pair| pair.public()))
+		.map_err(|_| format!("`{}` is neither an SS58 address nor a secret URI", key))
+}
+
+fn account_id(key: &str) -> Result<AccountId, String> {
+	public_key::<sr25519::Pair>(key)
+		.map(|public| <Signature as Verify>::Signer::from(public).into_account())
+}
+
+/// Build a ChainSpec from a participants description, see `ParticipantsSpec`
+pub fn participants_config(path: &Path) -> Result<ChainSpec, String> {
+	let file = File::open(path)
+		.map_err(|e| format!("Error opening participants file `{}`: {}", path.display(), e))?;
+	let spec: ParticipantsSpec = serde_json::from_reader(file)
+		.map_err(|e| format!("Error parsing participants file `{}`: {}", path.display(), e))?;
+
+	let authorities = spec
+		.authorities
+		.iter()
+		.map(|keys| {
+			Ok((
+				public_key::<sr25519::Pair>(&keys.aura)?,
+				public_key::<ed25519::Pair>(&keys.grandpa)?,
+			))
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+	if authorities.is_empty() {
+		return Err("A chain needs at least one authority".into());
+	}
+	let mut balances = Vec::new();
+	let mut users = Vec::new();
+	for participant in spec.participants.iter() {
+		let account = account_id(&participant.account)?;
+		if balances.iter().any(|(endowed, _)| endowed == &account) {
+			return Err(format!("Participant `{}` is listed twice", participant.account));
+		}
+		balances.push((account.clone(), participant.balance));
+		let noise_key = match &participant.noise_key {
+			Some(key) => Some(public_key::<ed25519::Pair>(key)?),
+			None => None,
+		};
+		if noise_key.is_some() || !participant.roles.is_empty() {
+			users.push((account, noise_key, participant.roles.clone(), participant.region));
+		}
+	}
+	let feeds = spec
+		.feeds
+		.iter()
+		.map(|feed| {
+			let publisher = account_id(&feed.publisher)?;
+			if !users.iter().any(|(address, ..)| address == &publisher) {
+				return Err(format!(
+					"Feed publisher `{}` is not a datdot user, give it a noise key or a role",
+					feed.publisher
+				));
+			}
+			if u32::from(feed.min_regions) > HostersPerPlan::get() {
+				return Err(format!(
+					"Feed `{}` asks for {} regions, but plans get only {} hosters",
+					feed.key,
+					feed.min_regions,
+					HostersPerPlan::get()
+				));
+			}
+			DatVerify::check_tree_root(&feed.merkle_root).map_err(|e| {
+				format!("Invalid merkle root of feed `{}`: {}", feed.key, <&'static str>::from(e))
+			})?;
+			let ranges = DatVerify::normalize_ranges(feed.ranges.clone(), feed.merkle_root.chunk_count())
+				.map_err(|e| {
+					format!("Invalid ranges of feed `{}`: {}", feed.key, <&'static str>::from(e))
+				})?;
+			Ok((
+				public_key::<ed25519::Pair>(&feed.key)?,
+				feed.merkle_root.clone(),
+				publisher,
+				ranges,
+				feed.min_regions,
+			))
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+	let boot_nodes = spec
+		.boot_nodes
+		.iter()
+		.map(|boot_node| {
+			boot_node
+				.parse::<MultiaddrWithPeerId>()
+				.map_err(|e| format!("Invalid boot node `{}`: {}", boot_node, e))
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+
+	let genesis = ParticipantsGenesis {
+		authorities,
+		sudo: account_id(&spec.sudo)?,
+		balances,
+		users,
+		feeds,
+	};
+	Ok(ChainSpec::from_genesis(
+		&spec.name,
+		&spec.id,
+		spec.chain_type,
+		move || genesis.build(),
+		boot_nodes,
+		None,
+		spec.protocol_id.as_deref(),
+		None,
+		None,
+	))
+}
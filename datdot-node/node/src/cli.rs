@@ -1,4 +1,6 @@
-use sc_cli::{RunCmd, Subcommand};
+use crate::service::Sealing;
+use sc_cli::{CliConfiguration, PruningParams, RunCmd, SharedParams};
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -8,4 +10,73 @@ pub struct Cli {
 
 	#[structopt(flatten)]
 	pub run: RunCmd,
+
+	/// How blocks are produced: `instant` seals a block for every transaction,
+	/// `manual` seals one on every `engine_createBlock` RPC call,
+	/// `aura` runs Aura slots with GRANDPA finality.
+	#[structopt(long = "sealing", default_value = "instant")]
+	pub sealing: Sealing,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Subcommand {
+	#[structopt(flatten)]
+	Base(sc_cli::Subcommand),
+
+	/// Build a chain spec from a JSON description of the chain's datdot participants.
+	BuildDatdotSpec(BuildDatdotSpecCmd),
+
+	/// Export the datdot users, feeds, plans, contracts, challenges and attestations
+	/// of a block as JSON.
+	ExportDatdotState(ExportDatdotStateCmd),
+
+	/// Build a dev chain spec whose genesis restores an exported datdot state.
+	ImportDatdotState(ImportDatdotStateCmd),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct BuildDatdotSpecCmd {
+	/// JSON file with the authorities, accounts, roles and feeds of the chain,
+	/// see `datdot-node/scripts/participants.example.json`.
+	#[structopt(long, parse(from_os_str))]
+	pub participants: PathBuf,
+
+	/// Print the chain spec with its genesis storage in raw format.
+	#[structopt(long)]
+	pub raw: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExportDatdotStateCmd {
+	/// Number or hash of the block to export the state of, the best block by default.
+	/// Blocks older than the pruning window need an archive node.
+	#[structopt(long, value_name = "BLOCK")]
+	pub at: Option<String>,
+
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+}
+
+impl CliConfiguration for ExportDatdotStateCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ImportDatdotStateCmd {
+	/// JSON file written by `export-datdot-state`.
+	#[structopt(long, parse(from_os_str))]
+	pub state: PathBuf,
+
+	/// Print the chain spec with its genesis storage in raw format.
+	#[structopt(long)]
+	pub raw: bool,
 }
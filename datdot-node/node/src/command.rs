@@ -1,5 +1,6 @@
 use crate::chain_spec;
-use crate::cli::Cli;
+use crate::cli::{Cli, Subcommand};
+use crate::datdot_state;
 use crate::service;
 use sc_cli::SubstrateCli;
 
@@ -46,15 +47,44 @@ impl SubstrateCli for Cli {
 /// Parse and run command line arguments
 pub fn run() -> sc_cli::Result<()> {
 	let cli = Cli::from_args();
+	let sealing = cli.sealing;
 
 	match &cli.subcommand {
-		Some(subcommand) => {
+		Some(Subcommand::Base(subcommand)) => {
 			let runner = cli.create_runner(subcommand)?;
-			runner.run_subcommand(subcommand, |config| Ok(new_full_start!(config)))
+			runner.run_subcommand(subcommand, |config| Ok(new_full_start!(config, sealing, None).0))
+		}
+		Some(Subcommand::BuildDatdotSpec(cmd)) => {
+			let spec = chain_spec::participants_config(&cmd.participants).map_err(sc_cli::Error::Input)?;
+			let json = sc_service::ChainSpec::as_json(&spec, cmd.raw).map_err(sc_cli::Error::Input)?;
+			println!("{}", json);
+			Ok(())
+		}
+		Some(Subcommand::ExportDatdotState(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let builder = new_full_start!(config, sealing, None).0;
+				let state = datdot_state::export_state(&**builder.client(), cmd.at.as_deref())
+					.map_err(sc_cli::Error::Input)?;
+				let json = serde_json::to_string_pretty(&state)
+					.map_err(|e| sc_cli::Error::Input(e.to_string()))?;
+				println!("{}", json);
+				Ok::<_, sc_cli::Error>(())
+			})
+		}
+		Some(Subcommand::ImportDatdotState(cmd)) => {
+			let spec = chain_spec::state_import_config(&cmd.state).map_err(sc_cli::Error::Input)?;
+			let json = sc_service::ChainSpec::as_json(&spec, cmd.raw).map_err(sc_cli::Error::Input)?;
+			println!("{}", json);
+			Ok(())
 		}
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
-			runner.run_node(service::new_light, service::new_full, runtime::VERSION)
+			runner.run_node(
+				|config| service::new_light(config, sealing),
+				|config| service::new_full(config, sealing),
+				runtime::VERSION,
+			)
 		}
 	}
 }
@@ -0,0 +1,109 @@
+//! Reads the datdot pallet state of a block from the node's database for
+//! `export-datdot-state`.
+//!
+//! Items are read straight from storage rather than through the runtime API, so
+//! blocks of any runtime version encoding the current pallet types can be exported.
+
+use codec::Decode;
+use runtime::{
+	dat_verify::{self, Role, StateDump},
+	opaque::Block,
+	Hash, Runtime,
+};
+use sc_client_api::{Backend, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	hashing::twox_128,
+	storage::{StorageData, StorageKey},
+};
+use sp_runtime::generic::BlockId;
+
+type UserId = <Runtime as dat_verify::Trait>::UserId;
+
+// length of a storage item prefix and of a `twox_64_concat` hash before its key
+const PREFIX_LEN: usize = 32;
+const TWOX_64_LEN: usize = 8;
+
+/// Exports the datdot state of the block with the number or hash, the best block
+/// if none is given
+pub fn export_state<B, C>(client: &C, at: Option<&str>) -> Result<StateDump<Runtime>, String>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B> + HeaderBackend<Block>,
+{
+	let at = match at {
+		Some(at) if at.starts_with("0x") => BlockId::Hash(
+			at.parse::<Hash>()
+				.map_err(|e| format!("Invalid block hash `{}`: {:?}", at, e))?,
+		),
+		Some(at) => BlockId::Number(
+			at.parse()
+				.map_err(|e| format!("Invalid block number `{}`: {}", at, e))?,
+		),
+		None => BlockId::Hash(client.info().best_hash),
+	};
+	if client.header(at.clone()).map_err(|e| e.to_string())?.is_none() {
+		return Err(format!("Block {:?} is not in the database", at));
+	}
+
+	Ok(StateDump {
+		users: values(client, &at, "GetUserByID")?,
+		// a given up role stays in storage with a `None` value
+		roles: pairs(client, &at, "Roles")?
+			.into_iter()
+			.map(|(key, value)| {
+				let mut key = &key.0[PREFIX_LEN + TWOX_64_LEN..];
+				let role = Role::decode(&mut key).map_err(|e| e.what())?;
+				let user_id = UserId::decode(&mut &key[TWOX_64_LEN..]).map_err(|e| e.what())?;
+				let held = Option::<u32>::decode(&mut &value.0[..]).map_err(|e| e.what())?;
+				Ok(held.map(|_| (role, user_id)))
+			})
+			.collect::<Result<Vec<_>, &str>>()
+			.map_err(|e| format!("Invalid Roles entry: {}", e))?
+			.into_iter()
+			.flatten()
+			.collect(),
+		hoster_regions: pairs(client, &at, "GetRegionByHoster")?
+			.into_iter()
+			.map(|(key, value)| {
+				let hoster_id = UserId::decode(&mut &key.0[PREFIX_LEN + TWOX_64_LEN..])?;
+				Ok((hoster_id, Decode::decode(&mut &value.0[..])?))
+			})
+			.collect::<Result<_, codec::Error>>()
+			.map_err(|e| format!("Invalid GetRegionByHoster entry: {}", e.what()))?,
+		feeds: values(client, &at, "GetFeedByID")?,
+		plans: values(client, &at, "GetPlanByID")?,
+		contracts: values(client, &at, "GetContractByID")?,
+		challenges: values(client, &at, "GetChallengeByID")?,
+		attestations: values(client, &at, "GetAttestationByID")?,
+	})
+}
+
+// all entries of a storage item of the pallet
+fn pairs<B, C>(
+	client: &C,
+	at: &BlockId<Block>,
+	item: &str,
+) -> Result<Vec<(StorageKey, StorageData)>, String>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B>,
+{
+	let prefix = [twox_128(b"DatVerify"), twox_128(item.as_bytes())].concat();
+	client
+		.storage_pairs(at, &StorageKey(prefix))
+		.map_err(|e| format!("Error reading {}: {}", item, e))
+}
+
+// decoded values of a storage map of the pallet, in key order
+fn values<B, C, V: Decode>(client: &C, at: &BlockId<Block>, item: &str) -> Result<Vec<V>, String>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B>,
+{
+	pairs(client, at, item)?
+		.into_iter()
+		.map(|(_, value)| V::decode(&mut &value.0[..]))
+		.collect::<Result<_, _>>()
+		.map_err(|e| format!("Invalid {} value: {}", item, e.what()))
+}
@@ -0,0 +1,10 @@
+//! Datdot node library: chain specs, service and CLI of the `datdot-node` binary,
+//! also used to run nodes in-process in the integration tests.
+
+pub mod chain_spec;
+#[macro_use]
+pub mod service;
+pub mod cli;
+pub mod command;
+pub mod datdot_state;
+pub mod metrics;
@@ -1,14 +1,8 @@
-//! Kitchen Node CLI library.
+//! Datdot Node CLI.
 
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
-mod chain_spec;
-#[macro_use]
-mod service;
-mod cli;
-mod command;
-
 fn main() -> sc_cli::Result<()> {
-	command::run()
+	datdot_node::command::run()
 }
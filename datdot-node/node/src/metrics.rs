@@ -0,0 +1,170 @@
+//! Prometheus metrics of datdot protocol activity.
+//!
+//! A node task follows the blocks imported as new best block: it counts the
+//! challenges and attestations in their events and reads the number of providers,
+//! active contracts and unmatched plans through the datdot runtime API.
+
+use codec::Decode;
+use futures::StreamExt;
+use log::debug;
+use pallet_datdot_rpc::DatdotRuntimeApi;
+use prometheus_endpoint::{
+	register, CounterVec, Gauge, GaugeVec, Opts, PrometheusError, Registry, U64,
+};
+use runtime::{
+	dat_verify::{Challenge, Contract, Plan, RawEvent, Role, User},
+	opaque::Block,
+	AccountId, Event, Hash, Runtime,
+};
+use sc_client_api::{Backend, BlockchainEvents, StorageProvider};
+use sp_api::ProvideRuntimeApi;
+use sp_core::{hashing::twox_128, storage::StorageKey};
+use sp_runtime::generic::BlockId;
+use std::sync::Arc;
+
+type EventRecords = Vec<frame_system::EventRecord<Event, Hash>>;
+
+/// Datdot metrics registered with the node's Prometheus registry
+pub struct Metrics {
+	providers: GaugeVec<U64>,
+	active_contracts: Gauge<U64>,
+	unmatched_plans: Gauge<U64>,
+	challenges: CounterVec<U64>,
+	attestations: CounterVec<U64>,
+}
+
+impl Metrics {
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			providers: register(
+				GaugeVec::new(
+					Opts::new("datdot_providers", "Number of users holding each role"),
+					&["role"],
+				)?,
+				registry,
+			)?,
+			active_contracts: register(
+				Gauge::new(
+					"datdot_active_contracts",
+					"Number of contracts being hosted, including those of leaving hosters",
+				)?,
+				registry,
+			)?,
+			unmatched_plans: register(
+				Gauge::new(
+					"datdot_unmatched_plans",
+					"Number of plans with fewer hosters than the replication minimum",
+				)?,
+				registry,
+			)?,
+			challenges: register(
+				CounterVec::new(
+					Opts::new(
+						"datdot_challenges_total",
+						"Proof-of-storage challenges issued, passed and failed",
+					),
+					&["outcome"],
+				)?,
+				registry,
+			)?,
+			attestations: register(
+				CounterVec::new(
+					Opts::new(
+						"datdot_attestations_total",
+						"Attestations requested, confirmed and failed",
+					),
+					&["outcome"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	fn count_events(&self, records: &EventRecords) {
+		for record in records {
+			let event = match &record.event {
+				Event::dat_verify(event) => event,
+				_ => continue,
+			};
+			match event {
+				RawEvent::NewProofOfStorageChallenge(..) => self.challenge("issued"),
+				// force resolving emits these as well, so the `ForceResolved` events are not counted
+				RawEvent::ProofOfStorageConfirmed(..) => self.challenge("passed"),
+				RawEvent::ProofOfStorageFailed(..) => self.challenge("failed"),
+				RawEvent::NewAttestation(..) => self.attestation("requested"),
+				RawEvent::AttestationReportConfirmed(..) => self.attestation("confirmed"),
+				RawEvent::AttestationReportFailed(..) => self.attestation("failed"),
+				_ => (),
+			}
+		}
+	}
+
+	fn challenge(&self, outcome: &str) {
+		self.challenges.with_label_values(&[outcome]).inc();
+	}
+
+	fn attestation(&self, outcome: &str) {
+		self.attestations.with_label_values(&[outcome]).inc();
+	}
+}
+
+/// Updates the metrics for every block imported as new best block, events of
+/// blocks that are later retracted stay counted
+pub async fn run<B, C>(client: Arc<C>, metrics: Metrics)
+where
+	B: Backend<Block>,
+	C: BlockchainEvents<Block> + StorageProvider<Block, B> + ProvideRuntimeApi<Block>,
+	C::Api: DatdotRuntimeApi<
+		Block,
+		AccountId,
+		u32,
+		Role,
+		User<Runtime>,
+		Plan<Runtime>,
+		Contract<Runtime>,
+		Challenge<Runtime>,
+	>,
+{
+	let events_key = StorageKey([twox_128(b"System"), twox_128(b"Events")].concat());
+	let mut imported = client.import_notification_stream();
+	while let Some(notification) = imported.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+		let at = BlockId::Hash(notification.hash);
+
+		match client.storage(&at, &events_key) {
+			Ok(Some(events)) => match EventRecords::decode(&mut &events.0[..]) {
+				Ok(records) => metrics.count_events(&records),
+				Err(e) => {
+					debug!(target: "datdot", "Undecodable events in block {}: {}", notification.hash, e.what())
+				}
+			},
+			Ok(None) => (),
+			Err(e) => {
+				debug!(target: "datdot", "Reading events of block {} failed: {}", notification.hash, e)
+			}
+		}
+
+		match client.runtime_api().protocol_stats(&at) {
+			Ok(stats) => {
+				let providers = [
+					("encoder", stats.encoders),
+					("hoster", stats.hosters),
+					("attestor", stats.attestors),
+				];
+				for (role, count) in providers.iter() {
+					metrics
+						.providers
+						.with_label_values(&[role])
+						.set((*count).into());
+				}
+				metrics.active_contracts.set(stats.active_contracts.into());
+				metrics.unmatched_plans.set(stats.unmatched_plans.into());
+			}
+			Err(e) => {
+				debug!(target: "datdot", "Querying protocol stats of block {} failed: {:?}", notification.hash, e)
+			}
+		}
+	}
+}
@@ -1,12 +1,20 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
+use sc_client_api::ExecutorProvider;
 use sc_consensus::LongestChain;
 use sc_executor::native_executor_instance;
 pub use sc_executor::NativeExecutor;
+use sc_finality_grandpa::{
+	FinalityProofProvider as GrandpaFinalityProofProvider, SharedVoterState,
+	StorageAndProofProvider,
+};
 use sc_network::config::DummyFinalityProofRequestBuilder;
 use sc_service::{error::Error as ServiceError, AbstractService, Configuration, ServiceBuilder};
+use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
 use sp_inherents::InherentDataProviders;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 // Our native executor instance.
 native_executor_instance!(
@@ -15,12 +23,48 @@ native_executor_instance!(
 	runtime::native_version,
 );
 
+/// How the node produces and finalizes blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sealing {
+	/// A block for every transaction, without finality.
+	Instant,
+	/// A block on every `engine_createBlock` RPC call, finalized with `engine_finalizeBlock`.
+	Manual,
+	/// Aura slots with GRANDPA finality, for networks of several nodes.
+	Aura,
+}
+
+impl FromStr for Sealing {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"instant" => Ok(Sealing::Instant),
+			"manual" => Ok(Sealing::Manual),
+			"aura" => Ok(Sealing::Aura),
+			_ => Err(format!("unknown sealing mode `{}`, expected `instant`, `manual` or `aura`", s)),
+		}
+	}
+}
+
 /// Starts a `ServiceBuilder` for a full service.
 ///
 /// Use this macro if you don't actually need the full service, but just the builder in order to
 /// be able to perform chain operations.
+///
+/// The manual seal RPCs are served when a command sink is passed.
 macro_rules! new_full_start {
-	($config:expr) => {{
+	($config:expr, $sealing:expr, $command_sink:expr) => {{
+		use std::sync::Arc;
+		use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
+		use sc_consensus_manual_seal::rpc::{EngineCommand, ManualSeal, ManualSealApi};
+
+		let sealing: crate::service::Sealing = $sealing;
+		let command_sink: Option<futures::channel::mpsc::Sender<EngineCommand<runtime::Hash>>> =
+			$command_sink;
+		let mut import_setup = None;
+		let inherent_data_providers = sp_inherents::InherentDataProviders::new();
+
 		let builder = sc_service::ServiceBuilder::new_full::<
 			runtime::opaque::Block,
 			runtime::RuntimeApi,
@@ -38,38 +82,97 @@ macro_rules! new_full_start {
 			))
 		})?
 		.with_import_queue(
-			|_config, client, _select_chain, _transaction_pool, spawn_task_handle, registry| {
-				Ok(sc_consensus_manual_seal::import_queue(
-					Box::new(client),
-					spawn_task_handle,
-					registry,
-				))
+			|_config, client, mut select_chain, _transaction_pool, spawn_task_handle, registry| {
+				match sealing {
+					crate::service::Sealing::Instant | crate::service::Sealing::Manual => Ok(sc_consensus_manual_seal::import_queue(
+						Box::new(client),
+						spawn_task_handle,
+						registry,
+					)),
+					crate::service::Sealing::Aura => {
+						let select_chain = select_chain.take()
+							.ok_or_else(|| sc_service::Error::SelectChainRequired)?;
+
+						let (grandpa_block_import, grandpa_link) = sc_finality_grandpa::block_import(
+							client.clone(),
+							&(client.clone() as Arc<_>),
+							select_chain,
+						)?;
+
+						let aura_block_import = sc_consensus_aura::AuraBlockImport::<_, _, _, AuraPair>::new(
+							grandpa_block_import.clone(), client.clone(),
+						);
+
+						let import_queue = sc_consensus_aura::import_queue::<_, _, _, AuraPair, _>(
+							sc_consensus_aura::slot_duration(&*client)?,
+							aura_block_import,
+							Some(Box::new(grandpa_block_import.clone())),
+							None,
+							client,
+							inherent_data_providers.clone(),
+							spawn_task_handle,
+							registry,
+						)?;
+
+						import_setup = Some((grandpa_block_import, grandpa_link));
+
+						Ok(import_queue)
+					}
+				}
 			},
-		)?;
+		)?
+		.with_rpc_extensions(|builder| -> Result<jsonrpc_core::IoHandler<sc_rpc::Metadata>, _> {
+			let mut io = jsonrpc_core::IoHandler::default();
+			io.extend_with(pallet_datdot_rpc::DatdotApi::to_delegate(
+				pallet_datdot_rpc::Datdot::new(builder.client().clone()),
+			));
+			io.extend_with(pallet_transaction_payment_rpc::TransactionPaymentApi::to_delegate(
+				pallet_transaction_payment_rpc::TransactionPayment::new(builder.client().clone()),
+			));
+			if let Some(command_sink) = &command_sink {
+				io.extend_with(ManualSealApi::to_delegate(ManualSeal::new(command_sink.clone())));
+			}
+			Ok(io)
+		})?;
 
-		builder
+		(builder, import_setup, inherent_data_providers)
 		}};
 }
 
 /// Builds a new service for a full client.
-pub fn new_full(config: Configuration) -> Result<impl AbstractService, ServiceError> {
-	let is_authority = config.role.is_authority();
+pub fn new_full(config: Configuration, sealing: Sealing) -> Result<impl AbstractService, ServiceError> {
+	let role = config.role.clone();
+	let force_authoring = config.force_authoring;
+	let name = config.network.node_name.clone();
+	let disable_grandpa = config.disable_grandpa;
 
 	// This variable is only used when ocw feature is enabled.
 	// Suppress the warning when ocw feature is not enabled.
 	#[allow(unused_variables)]
 	let dev_seed = config.dev_key_seed.clone();
 
-	// This isn't great. It includes the timestamp inherent in all blocks
-	// regardless of runtime.
-	let inherent_data_providers = InherentDataProviders::new();
-	inherent_data_providers
-		.register_provider(sp_timestamp::InherentDataProvider)
-		.map_err(Into::into)
-		.map_err(sp_consensus::error::Error::InherentData)?;
+	// the authorship task receives the commands of the manual seal RPCs
+	let (command_sink, commands_stream) = match sealing {
+		Sealing::Manual => {
+			let (sink, stream) = futures::channel::mpsc::channel(1000);
+			(Some(sink), Some(stream))
+		}
+		Sealing::Instant | Sealing::Aura => (None, None),
+	};
+
+	let (builder, mut import_setup, inherent_data_providers) =
+		new_full_start!(config, sealing, command_sink);
 
-	let builder = new_full_start!(config);
-	let service = builder.build_full()?;
+	let service = builder
+		.with_finality_proof_provider(|client, backend| match sealing {
+			Sealing::Instant | Sealing::Manual => Ok(Arc::new(()) as _),
+			Sealing::Aura => {
+				// GenesisAuthoritySetProvider is implemented for StorageAndProofProvider
+				let provider = client as Arc<dyn StorageAndProofProvider<_, _>>;
+				Ok(Arc::new(GrandpaFinalityProofProvider::new(backend, provider)) as _)
+			}
+		})?
+		.build_full()?;
 
 	// Initialize seed for signing transaction using off-chain workers
 	#[cfg(feature = "ocw")]
@@ -78,41 +181,160 @@ pub fn new_full(config: Configuration) -> Result<impl AbstractService, ServiceEr
 			service
 				.keystore()
 				.write()
-				.insert_ephemeral_from_seed_by_type::<runtime::offchain_demo::crypto::Pair>(
+				.insert_ephemeral_from_seed_by_type::<runtime::dat_verify::crypto::Pair>(
 					&seed,
-					runtime::offchain_demo::KEY_TYPE,
+					runtime::dat_verify::KEY_TYPE,
 				)
 				.expect("Dev Seed should always succeed.");
 		}
 	}
 
-	if is_authority {
-		let proposer =
-			sc_basic_authorship::ProposerFactory::new(
-				service.client(),
-				service.transaction_pool(),
-				service.prometheus_registry().as_ref(),
+	match sealing {
+		Sealing::Instant | Sealing::Manual => {
+			// This isn't great. It includes the timestamp inherent in all blocks
+			// regardless of runtime.
+			inherent_data_providers
+				.register_provider(sp_timestamp::InherentDataProvider)
+				.map_err(Into::into)
+				.map_err(sp_consensus::error::Error::InherentData)?;
+
+			if role.is_authority() {
+				let proposer =
+					sc_basic_authorship::ProposerFactory::new(
+						service.client(),
+						service.transaction_pool(),
+						service.prometheus_registry().as_ref(),
+					);
+
+				let select_chain = service
+					.select_chain()
+					.ok_or(ServiceError::SelectChainRequired)?;
+
+				match commands_stream {
+					Some(commands_stream) => {
+						let authorship_future = sc_consensus_manual_seal::run_manual_seal(
+							Box::new(service.client()),
+							proposer,
+							service.client(),
+							service.transaction_pool().pool().clone(),
+							commands_stream,
+							select_chain,
+							inherent_data_providers,
+						);
+
+						service.spawn_essential_task("manual-seal", authorship_future);
+					}
+					None => {
+						let authorship_future = sc_consensus_manual_seal::run_instant_seal(
+							Box::new(service.client()),
+							proposer,
+							service.client(),
+							service.transaction_pool().pool().clone(),
+							select_chain,
+							inherent_data_providers,
+						);
+
+						service.spawn_essential_task("instant-seal", authorship_future);
+					}
+				}
+			}
+		}
+		Sealing::Aura => {
+			let (block_import, grandpa_link) = import_setup.take().expect(
+				"Link Half and Block Import are present for Full Services or setup failed before. qed",
 			);
 
-		let authorship_future = sc_consensus_manual_seal::run_instant_seal(
-			Box::new(service.client()),
-			proposer,
-			service.client(),
-			service.transaction_pool().pool().clone(),
-			service
-				.select_chain()
-				.ok_or(ServiceError::SelectChainRequired)?,
-			inherent_data_providers,
-		);
+			if role.is_authority() {
+				let proposer = sc_basic_authorship::ProposerFactory::new(
+					service.client(),
+					service.transaction_pool(),
+					service.prometheus_registry().as_ref(),
+				);
 
-		service.spawn_essential_task("instant-seal", authorship_future);
-	};
+				let client = service.client();
+				let select_chain = service
+					.select_chain()
+					.ok_or(ServiceError::SelectChainRequired)?;
+
+				let can_author_with =
+					sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
+
+				let aura = sc_consensus_aura::start_aura::<_, _, _, _, _, AuraPair, _, _, _>(
+					sc_consensus_aura::slot_duration(&*client)?,
+					client,
+					select_chain,
+					block_import,
+					proposer,
+					service.network(),
+					inherent_data_providers.clone(),
+					force_authoring,
+					service.keystore(),
+					can_author_with,
+				)?;
+
+				// the Aura authoring task is considered essential, i.e. if it
+				// fails we take down the service with it.
+				service.spawn_essential_task("aura", aura);
+			}
+
+			// if the node isn't actively participating in consensus then it doesn't
+			// need a keystore, regardless of which protocol we use below.
+			let keystore = if role.is_authority() {
+				Some(service.keystore() as sp_core::traits::BareCryptoStorePtr)
+			} else {
+				None
+			};
+
+			let grandpa_config = sc_finality_grandpa::Config {
+				gossip_duration: Duration::from_millis(333),
+				justification_period: 512,
+				name: Some(name),
+				observer_enabled: false,
+				keystore,
+				is_authority: role.is_network_authority(),
+			};
+
+			if disable_grandpa {
+				sc_finality_grandpa::setup_disabled_grandpa(
+					service.client(),
+					&inherent_data_providers,
+					service.network(),
+				)?;
+			} else {
+				let grandpa_config = sc_finality_grandpa::GrandpaParams {
+					config: grandpa_config,
+					link: grandpa_link,
+					network: service.network(),
+					inherent_data_providers: inherent_data_providers.clone(),
+					telemetry_on_connect: Some(service.telemetry_on_connect_stream()),
+					voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
+					prometheus_registry: service.prometheus_registry(),
+					shared_voter_state: SharedVoterState::empty(),
+				};
+
+				// the GRANDPA voter task is considered infallible, i.e.
+				// if it fails we take down the service with it.
+				service.spawn_essential_task(
+					"grandpa-voter",
+					sc_finality_grandpa::run_grandpa_voter(grandpa_config)?,
+				);
+			}
+		}
+	}
+
+	if let Some(registry) = service.prometheus_registry() {
+		let metrics = crate::metrics::Metrics::register(&registry)
+			.map_err(|e| ServiceError::Other(format!("Registering datdot metrics failed: {}", e)))?;
+		service.spawn_task("datdot-metrics", crate::metrics::run(service.client(), metrics));
+	}
 
 	Ok(service)
 }
 
 /// Builds a new service for a light client.
-pub fn new_light(config: Configuration) -> Result<impl AbstractService, ServiceError> {
+pub fn new_light(config: Configuration, sealing: Sealing) -> Result<impl AbstractService, ServiceError> {
+	let inherent_data_providers = InherentDataProviders::new();
+
 	ServiceBuilder::new_light::<runtime::opaque::Block, runtime::RuntimeApi, Executor>(config)?
 		.with_select_chain(|_config, backend| Ok(LongestChain::new(backend.clone())))?
 		.with_transaction_pool(|builder| {
@@ -134,24 +356,62 @@ pub fn new_light(config: Configuration) -> Result<impl AbstractService, ServiceE
 		.with_import_queue_and_fprb(
 			|_config,
 			 client,
-			 _backend,
-			 _fetcher,
+			 backend,
+			 fetcher,
 			 _select_chain,
 			 _tx_pool,
 			 spawn_task_handle,
 			 registry| {
-				let finality_proof_request_builder =
-					Box::new(DummyFinalityProofRequestBuilder::default()) as Box<_>;
+				match sealing {
+					Sealing::Instant | Sealing::Manual => {
+						let finality_proof_request_builder =
+							Box::new(DummyFinalityProofRequestBuilder::default()) as Box<_>;
 
-				let import_queue = sc_consensus_manual_seal::import_queue(
-					Box::new(client),
-					spawn_task_handle,
-					registry,
-				);
+						let import_queue = sc_consensus_manual_seal::import_queue(
+							Box::new(client),
+							spawn_task_handle,
+							registry,
+						);
 
-				Ok((import_queue, finality_proof_request_builder))
+						Ok((import_queue, finality_proof_request_builder))
+					}
+					Sealing::Aura => {
+						let fetch_checker = fetcher
+							.map(|fetcher| fetcher.checker().clone())
+							.ok_or_else(|| "Trying to start light import queue without active fetch checker")?;
+						let grandpa_block_import = sc_finality_grandpa::light_block_import(
+							client.clone(),
+							backend,
+							&(client.clone() as Arc<_>),
+							Arc::new(fetch_checker),
+						)?;
+						let finality_proof_import = grandpa_block_import.clone();
+						let finality_proof_request_builder =
+							finality_proof_import.create_finality_proof_request_builder();
+
+						let import_queue = sc_consensus_aura::import_queue::<_, _, _, AuraPair, _>(
+							sc_consensus_aura::slot_duration(&*client)?,
+							grandpa_block_import,
+							None,
+							Some(Box::new(finality_proof_import)),
+							client,
+							inherent_data_providers.clone(),
+							spawn_task_handle,
+							registry,
+						)?;
+
+						Ok((import_queue, finality_proof_request_builder))
+					}
+				}
 			},
 		)?
-		.with_finality_proof_provider(|_client, _backend| Ok(Arc::new(()) as _))?
+		.with_finality_proof_provider(|client, backend| match sealing {
+			Sealing::Instant | Sealing::Manual => Ok(Arc::new(()) as _),
+			Sealing::Aura => {
+				// GenesisAuthoritySetProvider is implemented for StorageAndProofProvider
+				let provider = client as Arc<dyn StorageAndProofProvider<_, _>>;
+				Ok(Arc::new(GrandpaFinalityProofProvider::new(backend, provider)) as _)
+			}
+		})?
 		.build_light()
 }
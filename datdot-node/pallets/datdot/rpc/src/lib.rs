@@ -0,0 +1,115 @@
+//! JSON-RPC interface exposing the datdot runtime API under the `datdot_*` namespace.
+
+use std::sync::Arc;
+
+use codec::Codec;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+pub use pallet_datdot_runtime_api::DatdotApi as DatdotRuntimeApi;
+
+#[rpc]
+pub trait DatdotApi<BlockHash, AccountId, FeedId, Role, User, Plan, Contract, Challenge> {
+	/// Contracts the account takes part in as publisher, encoder or hoster.
+	#[rpc(name = "datdot_contractsByUser")]
+	fn contracts_by_user(&self, user: AccountId, at: Option<BlockHash>) -> Result<Vec<Contract>>;
+
+	/// Challenges the hoster account still has to answer.
+	#[rpc(name = "datdot_openChallengesByHoster")]
+	fn open_challenges_by_hoster(&self, hoster: AccountId, at: Option<BlockHash>) -> Result<Vec<Challenge>>;
+
+	/// Hosting plans published for the feed.
+	#[rpc(name = "datdot_plansByFeed")]
+	fn plans_by_feed(&self, feed: FeedId, at: Option<BlockHash>) -> Result<Vec<Plan>>;
+
+	/// Users currently holding the role (`Encoder`, `Hoster` or `Attestor`).
+	#[rpc(name = "datdot_activeProviders")]
+	fn active_providers(&self, role: Role, at: Option<BlockHash>) -> Result<Vec<User>>;
+}
+
+/// Implements the `datdot_*` RPCs by calling into the runtime API.
+pub struct Datdot<C, Block> {
+	client: Arc<C>,
+	_marker: std::marker::PhantomData<Block>,
+}
+
+impl<C, Block> Datdot<C, Block> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+/// Error code for failed runtime API calls.
+const RUNTIME_ERROR: i64 = 1;
+
+fn runtime_error(e: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: "Unable to query datdot state.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+impl<C, Block, AccountId, FeedId, Role, User, Plan, Contract, Challenge>
+	DatdotApi<<Block as BlockT>::Hash, AccountId, FeedId, Role, User, Plan, Contract, Challenge>
+	for Datdot<C, Block>
+where
+	Block: BlockT,
+	C: Send + Sync + 'static,
+	C: ProvideRuntimeApi<Block>,
+	C: HeaderBackend<Block>,
+	C::Api: DatdotRuntimeApi<Block, AccountId, FeedId, Role, User, Plan, Contract, Challenge>,
+	AccountId: Codec,
+	FeedId: Codec,
+	Role: Codec,
+	User: Codec,
+	Plan: Codec,
+	Contract: Codec,
+	Challenge: Codec,
+{
+	fn contracts_by_user(
+		&self,
+		user: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<Contract>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		api.contracts_by_user(&at, user).map_err(runtime_error)
+	}
+
+	fn open_challenges_by_hoster(
+		&self,
+		hoster: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<Challenge>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		api.open_challenges_by_hoster(&at, hoster).map_err(runtime_error)
+	}
+
+	fn plans_by_feed(
+		&self,
+		feed: FeedId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<Plan>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		api.plans_by_feed(&at, feed).map_err(runtime_error)
+	}
+
+	fn active_providers(
+		&self,
+		role: Role,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<User>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		api.active_providers(&at, role).map_err(runtime_error)
+	}
+}
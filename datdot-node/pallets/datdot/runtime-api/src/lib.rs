@@ -0,0 +1,47 @@
+//! Runtime API definition for querying datdot state.
+//!
+//! The API is generic over the pallet's types so the runtime can plug in
+//! `pallet_datdot::Contract<Runtime>` and friends directly.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Codec, Decode, Encode};
+use sp_std::prelude::*;
+
+/// Counts of the datdot state of a block, exported by the node as metrics.
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ProtocolStats {
+	pub encoders: u32,
+	pub hosters: u32,
+	pub attestors: u32,
+	/// Contracts being hosted, including those of leaving hosters.
+	pub active_contracts: u32,
+	/// Plans with fewer hosters than the replication minimum.
+	pub unmatched_plans: u32,
+}
+
+sp_api::decl_runtime_apis! {
+	#[api_version(2)]
+	pub trait DatdotApi<AccountId, FeedId, Role, User, Plan, Contract, Challenge> where
+		AccountId: Codec,
+		FeedId: Codec,
+		Role: Codec,
+		User: Codec,
+		Plan: Codec,
+		Contract: Codec,
+		Challenge: Codec,
+	{
+		/// Contracts the account takes part in as publisher, encoder or hoster.
+		fn contracts_by_user(user: AccountId) -> Vec<Contract>;
+		/// Challenges the hoster account still has to answer.
+		fn open_challenges_by_hoster(hoster: AccountId) -> Vec<Challenge>;
+		/// Hosting plans published for the feed.
+		fn plans_by_feed(feed: FeedId) -> Vec<Plan>;
+		/// Users currently holding the role.
+		fn active_providers(role: Role) -> Vec<User>;
+		/// Number of providers per role, active contracts and unmatched plans.
+		/// Available since version 2.
+		fn protocol_stats() -> ProtocolStats;
+	}
+}
@@ -6,13 +6,18 @@
 
 use sp_std::prelude::*;
 use sp_std::fmt::Debug;
-use sp_std::collections::btree_map::BTreeMap;
+use sp_std::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
 use frame_support::{
 	decl_module,
 	decl_storage,
 	decl_event,
 	decl_error,
 	debug::native,
+	dispatch::{
+		DispatchError,
+		DispatchResult,
+		DispatchResultWithPostInfo,
+	},
 	fail,
 	ensure,
 	Parameter,
@@ -26,13 +31,14 @@ use frame_support::{
 		EnsureOrigin,
 		Get,
 		Randomness,
+		Currency,
+		ReservableCurrency,
 		schedule::Named as ScheduleNamed,
 	},
 	weights::{
 		Pays,
-		DispatchClass::{
-			Operational,
-		}
+		PostDispatchInfo,
+		Weight,
 	},
 };
 use sp_std::convert::{
@@ -42,21 +48,36 @@ use frame_system::{
 	self as system,
 	ensure_signed,
 	ensure_root,
-	RawOrigin
+	RawOrigin,
+	offchain::{
+		AppCrypto,
+		CreateSignedTransaction,
+		SendSignedTransaction,
+		Signer,
+	},
 };
 use codec::{
 	Encode,
 	Decode,
 	Codec,
-	EncodeLike
+	EncodeLike,
+	FullCodec
 };
 use sp_core::{
 	ed25519,
+	crypto::KeyTypeId,
+	offchain::StorageKind,
 	H256,
 	H512,
 };
 use sp_runtime::{
 	RuntimeDebug,
+	RuntimeAppPublic,
+	offchain::{
+		http,
+		storage::StorageValueRef,
+		Duration,
+	},
 	traits::{
 		Verify,
 		CheckEqual,
@@ -64,25 +85,110 @@ use sp_runtime::{
 		SimpleBitOps,
 		MaybeDisplay,
 		TrailingZeroInput,
+		SaturatedConversion,
+		Saturating,
+		Zero,
+		IdentifyAccount,
 		AtLeast32Bit,
+		Bounded,
 		MaybeSerializeDeserialize,
 		Member
 	},
 };
-use sp_arithmetic::traits::{BaseArithmetic, One};
+use sp_arithmetic::traits::{BaseArithmetic, CheckedAdd, One};
 use sp_io::hashing::blake2_256;
 use rand_chacha::{rand_core::{RngCore, SeedableRng}, ChaChaRng};
 use ed25519::{Public, Signature};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+
+mod migration;
+use migration::Releases;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+/******************************************************************************
+  Offchain worker keys
+******************************************************************************/
+/// Key type of the accounts the offchain worker signs transactions with.
+/// A hoster's account key has to be in the node's keystore under this type.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"dat!");
+
+/// Base URL an attestor's offchain worker fetches a hoster's chunks from unless the
+/// local storage key of the hoster or the node-wide `datdot::hoster_endpoint` key overrides it.
+pub const DEFAULT_HOSTER_ENDPOINT: &[u8] = b"http://localhost:8080";
+/// Milliseconds an attestor waits for a chunk before reporting it as not served
+pub const CHUNK_REQUEST_TIMEOUT: u64 = 3_000;
+/// Milliseconds within which a chunk has to be served for the hoster to count as
+/// being in the attestor's region
+pub const NEARBY_LATENCY: u8 = 50;
+
+pub mod crypto {
+	use super::KEY_TYPE;
+	use sp_runtime::{
+		app_crypto::{app_crypto, sr25519},
+		MultiSignature,
+		MultiSigner,
+	};
+
+	app_crypto!(sr25519, KEY_TYPE);
+
+	pub struct DatdotAuthId;
+
+	impl frame_system::offchain::AppCrypto<MultiSigner, MultiSignature> for DatdotAuthId {
+		type RuntimeAppPublic = Public;
+		type GenericSignature = sp_core::sr25519::Signature;
+		type GenericPublic = sp_core::sr25519::Public;
+	}
+}
 
 /******************************************************************************
   The module's configuration trait
 ******************************************************************************/
-pub trait Trait: system::Trait{
+pub trait Trait: system::Trait + generic_asset::Trait + CreateSignedTransaction<Call<Self>>{
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+	/// Crypto the offchain worker signs its transactions with
+	type AuthorityId: AppCrypto<Self::Public, Self::Signature>;
 	type Hash:
 	Parameter + Member + MaybeSerializeDeserialize + Debug + MaybeDisplay + SimpleBitOps
 	+ Default + Copy + CheckEqual + sp_std::hash::Hash + AsRef<[u8]> + AsMut<[u8]>;
 	type Randomness: Randomness<<Self as system::Trait>::Hash>;
+	type Currency: ReservableCurrency<Self::AccountId>;
+	/// Amount reserved from a hoster while it holds the hoster role
+	type HosterBond: Get<BalanceOf<Self>>;
+	/// Blocks a leaving hoster keeps serving its contracts while they are handed over
+	type HosterNoticePeriod: Get<Self::BlockNumber>;
+	/// Maximum number of handovers of leaving hosters' contracts `on_initialize` retries per block
+	type MaxHandoversPerBlock: Get<u32>;
+	/// Blocks a hoster has to answer a proof-of-storage challenge, until root sets `Parameters`
+	type ChallengeDelay: Get<Self::BlockNumber>;
+	/// Blocks over which the audit rate of a hosting contract is measured, until root sets `Parameters`
+	type AuditPeriod: Get<Self::BlockNumber>;
+	/// Challenges issued to each hosting contract per audit period, unless its plan overrides it,
+	/// until root sets `Parameters`
+	type DefaultAuditsPerPeriod: Get<u32>;
+	/// Attestors assigned to each attestation request, until root sets `Parameters`
+	type AttestorsPerChallenge: Get<u32>;
+	/// Hosters a new plan is replicated to, until root sets `Parameters`
+	type HostersPerPlan: Get<u32>;
+	/// Maximum weight `on_initialize` spends on issuing audit challenges per block
+	type AuditWeightBudget: Get<Weight>;
+	/// Blocks resolved challenges and attestations and ended contracts are kept before pruning
+	type ResolvedRetention: Get<Self::BlockNumber>;
+	/// Maximum number of resolved items `on_initialize` prunes per block
+	type MaxPrunedPerBlock: Get<u32>;
+	/// Maximum number of chunk ranges a plan can select
+	type MaxRanges: Get<u32>;
+	/// Maximum number of roots in a feed's merkle root
+	type MaxRootChildren: Get<u32>;
+	/// Maximum number of chunks a challenge or attestation asks for
+	type MaxChallengeChunks: Get<u32>;
+	/// Amount reserved per encoded byte of a feed, plan, requested challenge or attestation,
+	/// returned when it is removed
+	type DepositPerByte: Get<BalanceOf<Self>>;
 	//ID TYPES---
 	type FeedId: Parameter + Member + AtLeast32Bit + BaseArithmetic + EncodeLike<u32> + Codec + Default + Copy +
 	MaybeSerializeDeserialize + Debug;
@@ -99,47 +205,166 @@ pub trait Trait: system::Trait{
 	// ---
 }
 
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type AssetBalanceOf<T> = <T as generic_asset::Trait>::Balance;
+
 
 /******************************************************************************
   Events
 ******************************************************************************/
 decl_event!(
 	pub enum Event<T> where
+	<T as system::Trait>::AccountId,
 	<T as Trait>::FeedId,
+	<T as Trait>::UserId,
 	<T as Trait>::ContractId,
 	<T as Trait>::PlanId,
 	<T as Trait>::ChallengeId,
-	<T as Trait>::AttestationId
+	<T as Trait>::AttestationId,
+	<T as system::Trait>::BlockNumber,
+	<T as generic_asset::Trait>::AssetId,
+	AssetBalance = AssetBalanceOf<T>
 	{
-		/// New data feed registered
-		NewFeed(FeedId),
+		/// Root changed the pallet parameters
+		ParametersSet(PalletParameters<BlockNumber>),
+		/// New user registered (User, Address)
+		NewUser(UserId, AccountId),
+		/// User took on a role
+		RoleRegistered(UserId, Role),
+		/// User gave up a role
+		RoleRemoved(UserId, Role),
+		/// New data feed registered (Feed, Feed key)
+		NewFeed(FeedId, FeedKey),
 		/// New hosting plan by publisher for selected feed (many possible plans per feed)
-		NewPlan(PlanId),
+		/// (Plan, Feed, Publisher)
+		NewPlan(PlanId, FeedId, UserId),
 		/// A new contract between publisher, encoder, and hoster (many contracts per plan)
-		/// (Encoder, Hoster,...)
-		NewContract(ContractId),
-		/// Hosting contract started
-		HostingStarted(ContractId),
+		/// (Contract, Plan, Feed key, Encoder, Encoder address, Hoster, Hoster address, Ranges)
+		NewContract(ContractId, PlanId, FeedKey, UserId, AccountId, UserId, AccountId, Ranges<ChunkIndex>),
+		/// Hosting contract started (Contract, Hoster)
+		HostingStarted(ContractId, UserId),
+		/// Hoster unregistered, its contracts are handed over until the notice period ends
+		HosterExitStarted(UserId, BlockNumber),
+		/// Replacement contract created for a contract of a leaving hoster (Retiring, Replacement)
+		HandoverStarted(ContractId, ContractId),
+		/// Replacement hoster started hosting, the retiring contract ended (Retiring, Replacement)
+		HandoverCompleted(ContractId, ContractId),
+		/// All contracts of a leaving hoster are handed over and its bond is released
+		HosterExited(UserId),
 		/// New proof-of-storage challenge
-		NewProofOfStorageChallenge(ChallengeId),
-		/// Proof-of-storage confirmed
-		ProofOfStorageConfirmed(ChallengeId),
-		/// Proof-of-storage not confirmed
-		ProofOfStorageFailed(ChallengeId),
+		/// (Challenge, Contract, Hoster, Hoster address, Feed key, Chunks, Deadline)
+		NewProofOfStorageChallenge(ChallengeId, ContractId, UserId, AccountId, FeedKey, Vec<ChunkIndex>, BlockNumber),
+		/// Proof-of-storage confirmed (Challenge, Hoster)
+		ProofOfStorageConfirmed(ChallengeId, UserId),
+		/// Proof-of-storage not confirmed (wrong proof or deadline passed) (Challenge, Hoster)
+		ProofOfStorageFailed(ChallengeId, UserId),
+		/// Publisher changed the audit rate of a plan
+		PlanAuditRateSet(PlanId, Option<u32>),
+		/// Publisher unpublished a plan, its contracts ended
+		PlanUnpublished(PlanId),
+		/// Publisher added to the hosting escrow of a plan (Plan, Asset, Amount)
+		PlanFunded(PlanId, AssetId, AssetBalance),
+		/// Hoster was paid from the plan escrow for a passed proof (Contract, Hoster, Asset, Amount)
+		HosterPaid(ContractId, UserId, AssetId, AssetBalance),
 		/// Attestation of retrievability requested
-		NewAttestation(AttestationId),
-		/// Proof of retrievability confirmed
-		AttestationReportConfirmed(AttestationId),
-		/// Data serving not verified
-		AttestationReportFailed(AttestationId),
+		/// (Attestation, Contract, Attestor, Attestor address, Feed key, Chunks)
+		NewAttestation(AttestationId, ContractId, UserId, AccountId, FeedKey, Vec<ChunkIndex>),
+		/// Proof of retrievability confirmed (Attestation, Hoster)
+		AttestationReportConfirmed(AttestationId, UserId),
+		/// Data serving not verified (Attestation, Hoster)
+		AttestationReportFailed(AttestationId, UserId),
+		/// Attestor observed the hoster in the region it declared
+		HosterRegionConfirmed(UserId, Region),
+		/// Attestor observed the hoster in a region other than the one declared
+		HosterRegionContradicted(UserId, Region),
+		/// Root ended a contract
+		ContractForceEnded(ContractId),
+		/// Root removed all roles of a user
+		UserRolesForceRemoved(UserId),
+		/// Root deleted a feed and its plans
+		FeedDeleted(FeedId),
+		/// Root resolved a challenge (Challenge, Passed)
+		ChallengeForceResolved(ChallengeId, bool),
+		/// Root resolved an attestation (Attestation, Passed)
+		AttestationForceResolved(AttestationId, bool),
 	}
 );
 
+/******************************************************************************
+  Errors
+******************************************************************************/
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// No contract with the given id
+		ContractNotFound,
+		/// Only the hoster of a contract can start hosting it
+		NotContractHoster,
+		/// Hosting can only start on a contract that is being encoded
+		ContractNotEncoding,
+		/// User does not hold the hoster role
+		NotHoster,
+		/// Hoster already unregistered and is handing over its contracts
+		HosterLeaving,
+		/// No plan with the given id
+		PlanNotFound,
+		/// Only the publisher of a plan can change it
+		NotPlanPublisher,
+		/// No challenge with the given id
+		ChallengeNotFound,
+		/// Challenge was already answered or its deadline passed
+		ChallengeNotOpen,
+		/// No attestation with the given id
+		AttestationNotFound,
+		/// Attestation was already reported on
+		AttestationNotOpen,
+		/// Only the assigned attestor can report on an attestation
+		NotAttestor,
+		/// No user with the given id
+		UserNotFound,
+		/// No feed with the given id
+		FeedNotFound,
+		/// Challenge delay, audit period, attestors per challenge and hosters per plan have to be at least one
+		InvalidParameters,
+		/// Every value of the id type is taken
+		IdsExhausted,
+		/// A plan has to select at least one chunk range
+		NoRanges,
+		/// More chunk ranges than `MaxRanges`
+		TooManyRanges,
+		/// A chunk range ends before it starts
+		InvertedRange,
+		/// A chunk range reaches past the last chunk of the feed
+		RangeBeyondFeed,
+		/// More roots than `MaxRootChildren`
+		TooManyRootChildren,
+		/// Not exactly one proof per chunk of the challenge
+		ProofCountMismatch,
+		/// Not exactly one report per chunk of the attestation
+		ReportCountMismatch,
+		/// Plan does not pay for hosting
+		PlanNotPaid,
+		/// Account cannot reserve the storage deposit
+		CannotReserve,
+		/// Contract already ended
+		ContractEnded,
+		/// Plan asks for more regions than it gets hosters
+		TooManyRegions,
+		/// Contract is not being hosted
+		ContractNotHosting,
+		/// User does not hold the encoder role
+		NotEncoder,
+		/// User does not hold the attestor role
+		NotRegisteredAttestor,
+	}
+}
+
 type RoleValue = Option<u32>;
 type ChunkIndex = u64;
+type Region = u8;
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
-enum Role {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Role {
 	Encoder,
 	Hoster,
 	Attestor
@@ -148,7 +373,8 @@ enum Role {
 type NoiseKey = Public;
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
-struct User<T: Trait> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct User<T: Trait> {
 	id: T::UserId,
 	address: T::AccountId,
 	noise_key: Option<NoiseKey>
@@ -157,7 +383,8 @@ struct User<T: Trait> {
 type FeedKey = Public;
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
-struct Feed<T: Trait> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Feed<T: Trait> {
 	id: T::FeedId,
 	publickey: FeedKey,
 	meta: TreeRoot
@@ -166,6 +393,7 @@ struct Feed<T: Trait> {
 type Ranges<C> = Vec<(C, C)>;
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct ParentHashInRoot {
 	hash: H256,
 	hash_number: u64,
@@ -173,12 +401,23 @@ pub struct ParentHashInRoot {
 }
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct TreeRoot {
 	signature: H512,
 	hash_type: u8, //2
 	children: Vec<ParentHashInRoot>
 }
 
+impl TreeRoot {
+	/// Number of chunks in the feed, up to the rightmost leaf under its roots
+	pub fn chunk_count(&self) -> u64 {
+		self.children.iter()
+			.map(|root| Node::leaves_up_to(root.hash_number))
+			.max()
+			.unwrap_or(0)
+	}
+}
+
 #[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
 pub struct TreeHashPayload {
 	hash_type: u8, //2
@@ -186,27 +425,92 @@ pub struct TreeHashPayload {
 }
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
-struct Plan<T: Trait> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(bound(
+	serialize = "T::PlanId: Serialize, T::FeedId: Serialize, T::UserId: Serialize, \
+		T::AssetId: Serialize, AssetBalanceOf<T>: Serialize",
+	deserialize = "T::PlanId: Deserialize<'de>, T::FeedId: Deserialize<'de>, T::UserId: Deserialize<'de>, \
+		T::AssetId: Deserialize<'de>, AssetBalanceOf<T>: Deserialize<'de>"
+)))]
+pub struct Plan<T: Trait> {
 	id: T::PlanId,
 	feed: T::FeedId,
 	publisher: T::UserId,
-	ranges: Ranges<ChunkIndex>
+	ranges: Ranges<ChunkIndex>,
+	// minimum number of distinct hoster regions holding a replica
+	min_regions: u8,
+	payment: Option<PlanPayment<T::AssetId, AssetBalanceOf<T>>>
 }
 
+// hosting payment of a plan in a generic asset, escrowed from the publisher
 #[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
-struct Contract<T: Trait> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct PlanPayment<AssetId, Balance> {
+	asset: AssetId,
+	// paid to the hoster for every passed proof of storage
+	per_proof: Balance,
+	// reserved from the publisher and not paid out yet
+	escrow: Balance
+}
+
+#[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ContractStatus {
+	Encoding,
+	Hosting,
+	// hoster is leaving, kept until the replacement contract starts hosting
+	Retiring,
+	Ended
+}
+
+impl Default for ContractStatus {
+	fn default() -> Self {
+		ContractStatus::Encoding
+	}
+}
+
+#[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Contract<T: Trait> {
 	id: T::ContractId,
 	plan: T::PlanId,
 	ranges: Ranges<ChunkIndex>,
 	encoder: T::UserId,
-	hoster: T::UserId
+	hoster: T::UserId,
+	status: ContractStatus
+}
+
+#[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct HosterExit<T: Trait> {
+	notice_ends: T::BlockNumber,
+	// contracts of the leaving hoster not yet taken over by a replacement
+	pending: Vec<T::ContractId>
+}
+
+#[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ChallengeStatus {
+	Open,
+	Passed,
+	Failed
+}
+
+impl Default for ChallengeStatus {
+	fn default() -> Self {
+		ChallengeStatus::Open
+	}
 }
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
-struct Challenge<T: Trait> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Challenge<T: Trait> {
 	id: T::ChallengeId,
 	contract: T::ContractId,
-	chunks: Vec<ChunkIndex>
+	chunks: Vec<ChunkIndex>,
+	// last block a proof is accepted in
+	deadline: T::BlockNumber,
+	status: ChallengeStatus
 }
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
@@ -221,14 +525,15 @@ impl Node {
 		Self::get_height(self.index)
 	}
 
+	// number of trailing one bits, at most 64 so user supplied indexes cannot overflow it
 	fn get_height(index : u64) -> u64 {
-		let mut bit_pointer : u64 = 1;
-		let mut cur_height : u64 = 0;
-		while (index | bit_pointer) == index {
-			cur_height += 1;
-			bit_pointer *= 2;
-		}
-		cur_height
+		(!index).trailing_zeros() as u64
+	}
+
+	//get the number of leaves up to and including the rightmost leaf under a node
+	fn leaves_up_to(index : u64) -> u64 {
+		let span = 2u64.saturating_pow(Self::get_height(index).try_into().unwrap_or(u32::max_value()));
+		index.saturating_add(span - 1) / 2 + 1
 	}
 
 	//get the index as if it were a leaf
@@ -324,18 +629,94 @@ impl Node {
 type Proof = Public;
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
-struct Attestation<T: Trait> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Attestation<T: Trait> {
 	id: T::AttestationId,
 	attestor: T::UserId,
-	contract: T::ContractId
+	contract: T::ContractId,
+	// chunks the attestor retrieves from the hoster
+	chunks: Vec<ChunkIndex>,
+	status: ChallengeStatus
 }
 
 #[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct Report {
-	location: u8,
+	// region the attestor measured from, None if its node has none configured
+	location: Option<Region>,
+	// milliseconds until the chunk was served (saturating), None if it was not
 	latency: Option<u8>
 }
 
+#[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct HosterRegion {
+	region: Region,
+	confirmations: u32,
+	contradictions: u32
+}
+
+// values root can tune without a runtime upgrade
+#[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct PalletParameters<BlockNumber> {
+	challenge_delay: BlockNumber,
+	audit_period: BlockNumber,
+	default_audits_per_period: u32,
+	attestors_per_challenge: u32,
+	// replication minimum of a plan
+	hosters_per_plan: u32
+}
+
+// outcome of all challenges and attestations of a hoster, kept after they are pruned
+#[derive(Decode, PartialEq, Eq, Encode, Clone, Default, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct HosterRecord {
+	proofs_passed: u32,
+	proofs_failed: u32,
+	attestations_passed: u32,
+	attestations_failed: u32
+}
+
+// stored items a deposit is held for
+#[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
+pub enum Deposited<T: Trait> {
+	Feed(T::FeedId),
+	Plan(T::PlanId),
+	Challenge(T::ChallengeId),
+	Attestation(T::AttestationId),
+	User(T::UserId)
+}
+
+#[derive(Decode, PartialEq, Eq, Encode, Clone, RuntimeDebug)]
+pub enum Prunable<T: Trait> {
+	Challenge(T::ChallengeId),
+	Attestation(T::AttestationId),
+	Contract(T::ContractId)
+}
+
+/// Stored datdot items of a block, as exported by the node's `export-datdot-state`
+/// and restored at genesis through the `state` genesis config
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Clone, RuntimeDebug)]
+#[serde(rename_all = "camelCase", bound(
+	serialize = "User<T>: Serialize, Feed<T>: Serialize, Plan<T>: Serialize, Contract<T>: Serialize, \
+		Challenge<T>: Serialize, Attestation<T>: Serialize, T::UserId: Serialize",
+	deserialize = "User<T>: Deserialize<'de>, Feed<T>: Deserialize<'de>, Plan<T>: Deserialize<'de>, \
+		Contract<T>: Deserialize<'de>, Challenge<T>: Deserialize<'de>, Attestation<T>: Deserialize<'de>, \
+		T::UserId: Deserialize<'de>"
+))]
+pub struct StateDump<T: Trait> {
+	pub users: Vec<User<T>>,
+	pub roles: Vec<(Role, T::UserId)>,
+	pub hoster_regions: Vec<(T::UserId, HosterRegion)>,
+	pub feeds: Vec<Feed<T>>,
+	pub plans: Vec<Plan<T>>,
+	pub contracts: Vec<Contract<T>>,
+	pub challenges: Vec<Challenge<T>>,
+	pub attestations: Vec<Attestation<T>>
+}
+
 /******************************************************************************
   Storage items/db
 ******************************************************************************/
@@ -358,8 +739,82 @@ decl_storage! {
 		pub Nonce: u64;
 		// LOOKUPS (created as neccesary)
 		pub GetIDByUser: map hasher(twox_64_concat) T::AccountId => Option<T::UserId>;
+		pub ContractsByHoster: double_map hasher(twox_64_concat) T::UserId, hasher(twox_64_concat) T::ContractId => ();
+		pub ContractsByEncoder: double_map hasher(twox_64_concat) T::UserId, hasher(twox_64_concat) T::ContractId => ();
+		pub ContractsByPlan: double_map hasher(twox_64_concat) T::PlanId, hasher(twox_64_concat) T::ContractId => ();
+		pub PlansByFeed: double_map hasher(twox_64_concat) T::FeedId, hasher(twox_64_concat) T::PlanId => ();
+		pub ChallengesByContract: double_map hasher(twox_64_concat) T::ContractId, hasher(twox_64_concat) T::ChallengeId => ();
+		pub GetRegionByHoster: map hasher(twox_64_concat) T::UserId => Option<HosterRegion>;
+		pub GetBondByHoster: map hasher(twox_64_concat) T::UserId => Option<BalanceOf<T>>;
+		pub GetExitByHoster: map hasher(twox_64_concat) T::UserId => Option<HosterExit<T>>;
+		// replacement contract => retiring contract
+		pub GetHandoverByContract: map hasher(twox_64_concat) T::ContractId => Option<T::ContractId>;
+		pub GetAuditRateByPlan: map hasher(twox_64_concat) T::PlanId => Option<u32>;
+		// contracts whose hoster confirmed `hosting_starts`, audited in on_initialize
+		pub HostingContracts get(fn hosting_contracts): Vec<T::ContractId>;
+		pub ChallengesByDeadline: double_map hasher(twox_64_concat) T::BlockNumber, hasher(twox_64_concat) T::ChallengeId => ();
+		// set by root, the trait constants apply until then
+		pub Parameters: Option<PalletParameters<T::BlockNumber>>;
+		pub GetDepositByItem: map hasher(twox_64_concat) Deposited<T> => Option<(T::AccountId, BalanceOf<T>)>;
+		pub GetRecordByHoster: map hasher(twox_64_concat) T::UserId => HosterRecord;
+		// resolved items in the order they were resolved, with the block they can be pruned at
+		pub PruneQueue: map hasher(twox_64_concat) u64 => Option<(T::BlockNumber, Prunable<T>)>;
+		pub PruneQueueHead: u64;
+		pub PruneQueueTail: u64;
 		// ROLES ARRAY
 		pub Roles: double_map hasher(twox_64_concat) Role, hasher(twox_64_concat) T::UserId => RoleValue;
+		// encoding release of the stored types, see `migration`
+		StorageVersion build(|_| migration::CURRENT_RELEASE): Releases;
+	}
+	add_extra_genesis {
+		/// Users registered at genesis: (address, noise key, roles, hoster region).
+		/// Genesis hosters hold no bond.
+		config(users): Vec<(T::AccountId, Option<NoiseKey>, Vec<Role>, Region)>;
+		/// Feeds published at genesis, each with one plan: (feed key, merkle root, publisher address,
+		/// ranges, min regions). The publisher has to be one of `users`, the ranges are normalized
+		/// as in `publish_feed_and_plan` and invalid ones fail the genesis build.
+		config(feeds): Vec<(FeedKey, TreeRoot, T::AccountId, Ranges<ChunkIndex>, u8)>;
+		/// Items of an exported state restored before `users` and `feeds` are added,
+		/// see `restore_state`.
+		config(state): Option<StateDump<T>>;
+		build(|config: &GenesisConfig<T>| {
+			if let Some(state) = &config.state {
+				<Module<T>>::restore_state(state);
+			}
+			for (address, noise_key, roles, region) in config.users.iter() {
+				<Module<T>>::reg_user(address.clone(), noise_key.clone(), false)
+					.expect("genesis users fit the user id type; qed");
+				let user_id = <GetIDByUser<T>>::get(address).expect("user was just registered; qed");
+				for role in roles.iter() {
+					<Roles<T>>::insert(role, user_id, RoleValue::Some(0));
+					if role == &Role::Hoster {
+						<GetRegionByHoster<T>>::insert(user_id, HosterRegion {
+							region: *region,
+							..Default::default()
+						});
+					}
+				}
+			}
+			for (publickey, meta, publisher, ranges, min_regions) in config.feeds.iter() {
+				let publisher_id = <GetIDByUser<T>>::get(publisher)
+					.expect("genesis feed publishers are genesis users; qed");
+				<Module<T>>::check_tree_root(meta)
+					.unwrap_or_else(|e| panic!("Invalid merkle root of genesis feed {:?}: {:?}", publickey, e));
+				let ranges = <Module<T>>::normalize_ranges(ranges.clone(), meta.chunk_count())
+					.unwrap_or_else(|e| panic!("Invalid ranges of genesis feed {:?}: {:?}", publickey, e));
+				let plan_id = <Module<T>>::insert_feed_and_plan(
+					None,
+					publisher_id,
+					*publickey,
+					meta.clone(),
+					ranges,
+					*min_regions,
+					None
+				).unwrap_or_else(|e| panic!("Invalid genesis feed {:?}: {:?}", publickey, e));
+				<Module<T>>::make_new_contract(None, None, Some(plan_id))
+					.expect("genesis contracts fit the contract id type; qed");
+			}
+		});
 	}
 }
 
@@ -368,135 +823,185 @@ decl_storage! {
 ******************************************************************************/
 decl_module!{
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		/// Amount reserved from a hoster while it holds the hoster role
+		const HosterBond: BalanceOf<T> = T::HosterBond::get();
+		/// Blocks a leaving hoster keeps serving its contracts while they are handed over
+		const HosterNoticePeriod: T::BlockNumber = T::HosterNoticePeriod::get();
+		/// Maximum number of handovers retried per block
+		const MaxHandoversPerBlock: u32 = T::MaxHandoversPerBlock::get();
+		/// Maximum weight `on_initialize` spends on issuing audit challenges per block
+		const AuditWeightBudget: Weight = T::AuditWeightBudget::get();
+		/// Blocks resolved items are kept before pruning
+		const ResolvedRetention: T::BlockNumber = T::ResolvedRetention::get();
+		/// Maximum number of resolved items pruned per block
+		const MaxPrunedPerBlock: u32 = T::MaxPrunedPerBlock::get();
+		/// Maximum number of chunk ranges a plan can select
+		const MaxRanges: u32 = T::MaxRanges::get();
+		/// Maximum number of roots in a feed's merkle root
+		const MaxRootChildren: u32 = T::MaxRootChildren::get();
+		/// Maximum number of chunks a challenge or attestation asks for
+		const MaxChallengeChunks: u32 = T::MaxChallengeChunks::get();
+		/// Amount reserved per encoded byte of a stored user, feed, plan, challenge or attestation
+		const DepositPerByte: BalanceOf<T> = T::DepositPerByte::get();
+
 		fn deposit_event() = default;
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		fn offchain_worker(_n: T::BlockNumber) {
+			Self::answer_challenges();
+			Self::attest_retrievability();
+		}
+
+		fn on_runtime_upgrade() -> Weight {
+			migration::migrate::<T>()
+		}
+
+		fn on_initialize(n: T::BlockNumber) -> Weight {
+			Self::process_hoster_exits(n)
+				.saturating_add(Self::expire_challenges(n))
+				.saturating_add(Self::issue_audits())
+				.saturating_add(Self::prune_resolved(n))
+		}
+
+		#[weight = 100000] //todo weight
 		fn new_user(origin){
 			let user_address = ensure_signed(origin)?;
-			Self::reg_user(user_address, None);
+			Self::reg_user(user_address, None, true)?;
 		}
 
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn register_encoder(origin, noise_key: NoiseKey){
 			let user_address = ensure_signed(origin)?;
 			if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
-				Self::reg_user(user_address, Some(noise_key));
+				Self::reg_user(user_address, Some(noise_key), true)?;
 				<Roles<T>>::insert(Role::Encoder, user_id, RoleValue::Some(0));
-				Self::make_new_contract(Some(user_id.clone()), None, None);
+				Self::deposit_event(RawEvent::RoleRegistered(user_id, Role::Encoder));
+				Self::make_new_contract(Some(user_id.clone()), None, None)?;
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
-		fn register_hoster(origin, noise_key: NoiseKey){
+		#[weight = 100000] //todo weight
+		fn register_hoster(origin, noise_key: NoiseKey, region: Region){
 			let user_address = ensure_signed(origin)?;
 			if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
-				Self::reg_user(user_address, Some(noise_key));
+				ensure!(!<GetExitByHoster<T>>::contains_key(&user_id), Error::<T>::HosterLeaving);
+				// dispatches are not transactional, so everything that can fail is checked before
+				// the first write, the hoster may be matched with a plan right away
+				ensure!(<GetNextContractID<T>>::get() < T::ContractId::max_value(), Error::<T>::IdsExhausted);
+				let bonded = <GetBondByHoster<T>>::contains_key(&user_id);
+				ensure!(
+					bonded || T::Currency::can_reserve(&user_address, T::HosterBond::get()),
+					Error::<T>::CannotReserve
+				);
+				if !bonded {
+					let bond = T::HosterBond::get();
+					T::Currency::reserve(&user_address, bond)?;
+					<GetBondByHoster<T>>::insert(user_id, bond);
+				}
+				Self::reg_user(user_address, Some(noise_key), true)?;
+				<GetRegionByHoster<T>>::insert(user_id, HosterRegion {
+					region: region,
+					..Default::default()
+				});
 				<Roles<T>>::insert(Role::Hoster, user_id, RoleValue::Some(0));
-				Self::make_new_contract(None, Some(user_id.clone()), None);
+				Self::deposit_event(RawEvent::RoleRegistered(user_id, Role::Hoster));
+				Self::make_new_contract(None, Some(user_id.clone()), None)?;
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn register_attestor(origin){
 			let user_address = ensure_signed(origin)?;
 			if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
 				<Roles<T>>::insert(Role::Attestor, user_id, RoleValue::Some(0));
+				Self::deposit_event(RawEvent::RoleRegistered(user_id, Role::Attestor));
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn unregister_encoder(origin){
 			let user_address = ensure_signed(origin)?;
 			if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
+				ensure!(<Roles<T>>::get(Role::Encoder, user_id).is_some(), Error::<T>::NotEncoder);
 				<Roles<T>>::insert(Role::Encoder, user_id, RoleValue::None);
+				Self::deposit_event(RawEvent::RoleRemoved(user_id, Role::Encoder));
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn unregister_hoster(origin){
 			let user_address = ensure_signed(origin)?;
 			if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
+				ensure!(<Roles<T>>::get(Role::Hoster, user_id).is_some(), Error::<T>::NotHoster);
+				ensure!(!<GetExitByHoster<T>>::contains_key(&user_id), Error::<T>::HosterLeaving);
 				<Roles<T>>::insert(Role::Hoster, user_id, RoleValue::None);
+				Self::deposit_event(RawEvent::RoleRemoved(user_id, Role::Hoster));
+				Self::start_hoster_exit(user_id);
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn unregister_attestor(origin){
 			let user_address = ensure_signed(origin)?;
 			if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
+				ensure!(<Roles<T>>::get(Role::Attestor, user_id).is_some(), Error::<T>::NotRegisteredAttestor);
 				<Roles<T>>::insert(Role::Attestor, user_id, RoleValue::None);
+				Self::deposit_event(RawEvent::RoleRemoved(user_id, Role::Attestor));
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
-		fn publish_feed_and_plan(origin, merkle_root: (Public, TreeHashPayload, H512), ranges: Ranges<ChunkIndex>){
+		#[weight = 100000] //todo weight
+		fn publish_feed_and_plan(
+			origin,
+			merkle_root: (Public, TreeHashPayload, H512),
+			ranges: Ranges<ChunkIndex>,
+			min_regions: u8,
+			payment: Option<PlanPayment<T::AssetId, AssetBalanceOf<T>>>
+		){
 			let user_address = ensure_signed(origin)?;
 			if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
-				let mut feed_id : T::FeedId;
-				let mut plan_id : T::PlanId;
-			//TODO -- currently re-registering existing feeds creates a new FeedID, should lookup first.
-			let next_feed_id = <GetNextFeedID<T>>::get();
-				let new_feed = Feed {
-					id: next_feed_id.clone(),
-					publickey: merkle_root.0,
-					meta: TreeRoot {
-						signature: merkle_root.2,
-						hash_type: merkle_root.1.hash_type,
-						children: merkle_root.1.children
-					}
+				let meta = TreeRoot {
+					signature: merkle_root.2,
+					hash_type: merkle_root.1.hash_type,
+					children: merkle_root.1.children
 				};
-				<GetFeedByID<T>>::insert(next_feed_id, new_feed.clone());
-				feed_id = next_feed_id.clone();
-				<GetNextFeedID<T>>::put(next_feed_id+One::one());
-			let next_plan_id = <GetNextPlanID<T>>::get();
-				let new_plan = Plan::<T> {
-					id: next_plan_id.clone(),
-					publisher: user_id,
-					feed: feed_id,
-					ranges: ranges
-				};
-				<GetPlanByID<T>>::insert(next_plan_id, new_plan.clone());
-				plan_id = next_plan_id.clone();
-				<GetNextPlanID<T>>::put(next_plan_id+One::one());
-			Self::make_new_contract(None, None, Some(plan_id.clone()));
-			Self::deposit_event(RawEvent::NewFeed(feed_id));
-			Self::deposit_event(RawEvent::NewPlan(plan_id.clone()));
+				Self::check_tree_root(&meta)?;
+				let ranges = Self::normalize_ranges(ranges, meta.chunk_count())?;
+				let plan_id = Self::insert_feed_and_plan(Some(&user_address), user_id, merkle_root.0, meta, ranges, min_regions, payment)?;
+				Self::make_new_contract(None, None, Some(plan_id.clone()))?;
 			} else {
 				//some err
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn encoding_done(origin, contract_id: T::ContractId ){
 			let user_address = ensure_signed(origin)?;
 			// DB.contractsEncoded.push(contractID)
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn hosting_starts(origin, contract_id: T::ContractId ){
 			let user_address = ensure_signed(origin)?;
-			// DB.contractsHosted.push(contractID)
+			let mut contract = <GetContractByID<T>>::get(&contract_id).ok_or(Error::<T>::ContractNotFound)?;
+			ensure!(<GetIDByUser<T>>::get(&user_address) == Some(contract.hoster), Error::<T>::NotContractHoster);
+			ensure!(contract.status == ContractStatus::Encoding, Error::<T>::ContractNotEncoding);
+			contract.status = ContractStatus::Hosting;
+			<GetContractByID<T>>::insert(contract_id, contract.clone());
+			<HostingContracts<T>>::mutate(|contracts| contracts.push(contract_id));
 			// const HostingStarted = { event: { data: [contractID], method: 'HostingStarted' } }
 			// handlers.forEach(handler => handler([HostingStarted]))
-			Self::deposit_event(RawEvent::HostingStarted(contract_id));
+			Self::deposit_event(RawEvent::HostingStarted(contract_id, contract.hoster));
+			Self::complete_handover(contract_id);
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
 		fn request_proof_of_storage_challenge(origin, contract_id: T::ContractId ){
 			let user_address = ensure_signed(origin)?;
 			if let Some(contract) = <GetContractByID<T>>::get(&contract_id){
-				let ranges = contract.ranges;
-				let random_chunks = Self::random_from_ranges(ranges);
-				let challenge_id = <GetNextChallengeID<T>>::get();
-				let challenge = Challenge::<T> {
-					id: challenge_id.clone(),
-					contract: contract_id,
-					chunks: random_chunks
-				};
-				<GetChallengeByID<T>>::insert(challenge_id, challenge.clone());
-				<GetNextChallengeID<T>>::put(challenge_id.clone()+One::one());
-				Self::deposit_event(RawEvent::NewProofOfStorageChallenge(challenge_id.clone()));
+				Self::issue_challenge(Some(&user_address), contract)?;
 				/*
 				const ranges = DB.contracts[contractID - 1].ranges // [ [0, 3], [5, 7] ]
 				const chunks = ranges.map(range => getRandomInt(range[0], range[1] + 1))
@@ -512,11 +1017,15 @@ decl_module!{
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
-		fn submit_proof_of_storage(origin, challenge_id: T::ChallengeId, proofs: Vec<Proof> ){
+		#[weight = 100000] //todo weight
+		fn submit_proof_of_storage(origin, challenge_id: T::ChallengeId, proofs: Vec<Proof> ) -> DispatchResultWithPostInfo {
 			let user_address = ensure_signed(origin)?;
 			let mut success: bool = true;
 			if let Some(challenge) = <GetChallengeByID<T>>::get(&challenge_id){
+				ensure!(challenge.status == ChallengeStatus::Open, Error::<T>::ChallengeNotOpen);
+				ensure!(proofs.len() == challenge.chunks.len(), Error::<T>::ProofCountMismatch);
+				let contract = <GetContractByID<T>>::get(&challenge.contract).ok_or(Error::<T>::ContractNotFound)?;
+				ensure!(<GetIDByUser<T>>::get(&user_address) == Some(contract.hoster), Error::<T>::NotContractHoster);
 				for proof in proofs {
 					if Self::validate_proof(proof.clone(), challenge.clone()){
 						success = success && true;
@@ -524,11 +1033,10 @@ decl_module!{
 						success = success && false;
 					}
 				}
-				if success {
-					Self::deposit_event(RawEvent::ProofOfStorageConfirmed(challenge_id.clone()));
-				} else {
-					Self::deposit_event(RawEvent::ProofOfStorageFailed(challenge_id.clone()));
-				}
+				Self::resolve_challenge(challenge_id, challenge, contract.hoster, success);
+				// hosters only pay for wrong proofs
+				let pays_fee = if success { Pays::No } else { Pays::Yes };
+				Ok(PostDispatchInfo { actual_weight: None, pays_fee: pays_fee })
 			/*
 			const challenge = DB.challenges[challengeID - 1]
 		    const isValid = validateProof(proof, challenge)
@@ -541,22 +1049,88 @@ decl_module!{
 		    handlers.forEach(handler => handler([proofValidation]))
 			*/
 			} else {
-				// TODO invalid challenge
+				fail!(Error::<T>::ChallengeNotFound);
 			}
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
+		#[weight = 100000] //todo weight
+		fn set_plan_audit_rate(origin, plan_id: T::PlanId, audits_per_period: Option<u32>){
+			let user_address = ensure_signed(origin)?;
+			let plan = <GetPlanByID<T>>::get(&plan_id).ok_or(Error::<T>::PlanNotFound)?;
+			ensure!(<GetIDByUser<T>>::get(&user_address) == Some(plan.publisher), Error::<T>::NotPlanPublisher);
+			match audits_per_period {
+				Some(audits) => <GetAuditRateByPlan<T>>::insert(plan_id, audits),
+				None => <GetAuditRateByPlan<T>>::remove(plan_id),
+			}
+			Self::deposit_event(RawEvent::PlanAuditRateSet(plan_id, audits_per_period));
+		}
+
+		#[weight = 100000] //todo weight
+		fn fund_plan(origin, plan_id: T::PlanId, amount: AssetBalanceOf<T>){
+			let user_address = ensure_signed(origin)?;
+			let mut plan = <GetPlanByID<T>>::get(&plan_id).ok_or(Error::<T>::PlanNotFound)?;
+			ensure!(<GetIDByUser<T>>::get(&user_address) == Some(plan.publisher), Error::<T>::NotPlanPublisher);
+			let mut payment = plan.payment.ok_or(Error::<T>::PlanNotPaid)?;
+			<generic_asset::Module<T>>::reserve(&payment.asset, &user_address, amount)?;
+			payment.escrow = payment.escrow.saturating_add(amount);
+			Self::deposit_event(RawEvent::PlanFunded(plan_id, payment.asset, amount));
+			plan.payment = Some(payment);
+			<GetPlanByID<T>>::insert(plan_id, plan);
+		}
+
+		#[weight = 100000] //todo weight
+		fn unpublish_plan(origin, plan_id: T::PlanId){
+			let user_address = ensure_signed(origin)?;
+			let plan = <GetPlanByID<T>>::get(&plan_id).ok_or(Error::<T>::PlanNotFound)?;
+			ensure!(<GetIDByUser<T>>::get(&user_address) == Some(plan.publisher), Error::<T>::NotPlanPublisher);
+			Self::remove_plan(plan_id, plan);
+			Self::deposit_event(RawEvent::PlanUnpublished(plan_id));
+		}
+
+		#[weight = 100000] //todo weight
 		fn request_attestation(origin, contract_id: T::ContractId ){
 			let user_address = ensure_signed(origin)?;
-			if let Some(rand_attestor) = Self::get_random_of_role(&[0], &Role::Attestor, 1).pop(){
-				let attestation_id = <GetNextAttestationID<T>>::get();
-				let attestation = Attestation::<T> {
-					id: attestation_id.clone(),
-					attestor: rand_attestor,
-					contract: contract_id
-				};
+			let contract = <GetContractByID<T>>::get(&contract_id).ok_or(Error::<T>::ContractNotFound)?;
+			// sampled without replacement, so every attestor gets its own attestation
+			let rand_attestors = Self::get_random_of_role(
+				&[0],
+				&Role::Attestor,
+				Self::parameters().attestors_per_challenge
+			);
+			// dispatches are not transactional, so the ids and deposits of all attestations
+			// are checked before the first one is stored
+			let first_id = <GetNextAttestationID<T>>::get();
+			ensure!(
+				first_id.checked_add(&T::AttestationId::from(rand_attestors.len() as u32)).is_some(),
+				Error::<T>::IdsExhausted
+			);
+			let chunks = Self::random_from_ranges(contract.ranges.clone());
+			let attestations : Vec<Attestation<T>> = rand_attestors.into_iter()
+				.enumerate()
+				.map(|(i, attestor)| Attestation::<T> {
+					id: first_id + T::AttestationId::from(i as u32),
+					attestor: attestor,
+					contract: contract_id,
+					chunks: chunks.clone(),
+					status: ChallengeStatus::Open
+				})
+				.collect();
+			let deposit = attestations.iter().fold(Zero::zero(), |total: BalanceOf<T>, attestation|{
+				total.saturating_add(Self::deposit_of(attestation.encoded_size()))
+			});
+			ensure!(T::Currency::can_reserve(&user_address, deposit), Error::<T>::CannotReserve);
+			for attestation in attestations {
+				let attestation_id = Self::next_id::<GetNextAttestationID<T>, _>()?;
+				Self::hold_deposit(&user_address, Deposited::Attestation(attestation_id), attestation.encoded_size())?;
 				<GetAttestationByID<T>>::insert(attestation_id, attestation.clone());
-				Self::deposit_event(RawEvent::NewAttestation(attestation_id.clone()));
+				Self::deposit_event(RawEvent::NewAttestation(
+					attestation_id.clone(),
+					contract_id,
+					attestation.attestor,
+					Self::address_of(&attestation.attestor),
+					Self::feed_key_of_plan(&contract.plan),
+					attestation.chunks
+				));
 			}
 			/*
 			const [ attestorID ] = getRandom(DB.attestors)
@@ -568,16 +1142,24 @@ decl_module!{
 			*/
 		}
 
-		#[weight = (100000, Operational, Pays::No)] //todo weight
-		fn submit_attestation_report(origin, attestation_id: T::AttestationId, reports: Vec<Report> ){
+		#[weight = 100000] //todo weight
+		fn submit_attestation_report(origin, attestation_id: T::AttestationId, reports: Vec<Report> ) -> DispatchResultWithPostInfo {
 			let user_address = ensure_signed(origin)?;
 			let mut success: bool = true;
 			if let Some(attestation) = <GetAttestationByID<T>>::get(&attestation_id){
+				ensure!(attestation.status == ChallengeStatus::Open, Error::<T>::AttestationNotOpen);
+				ensure!(reports.len() == attestation.chunks.len(), Error::<T>::ReportCountMismatch);
+				ensure!(<GetIDByUser<T>>::get(&user_address) == Some(attestation.attestor), Error::<T>::NotAttestor);
+				let hoster_option = <GetContractByID<T>>::get(&attestation.contract)
+					.map(|contract| contract.hoster);
 				for report in reports {
 					match report.latency {
-						Some(_) => {
+						Some(latency) => {
 							// report passed
 							success = success && true;
+							if let (Some(hoster_id), Some(location)) = (hoster_option, report.location) {
+								Self::record_region_report(hoster_id, location, latency);
+							}
 						},
 						_ => {
 							// report failed
@@ -585,11 +1167,12 @@ decl_module!{
 						}
 					}
 				}
-				if success {
-					Self::deposit_event(RawEvent::AttestationReportConfirmed(attestation_id.clone()));
-				} else {
-					Self::deposit_event(RawEvent::AttestationReportFailed(attestation_id.clone()));
-				}
+				Self::resolve_attestation(attestation_id, attestation, hoster_option, success);
+				// the assigned attestor only pays for failed reports
+				let pays_fee = if success { Pays::No } else { Pays::Yes };
+				Ok(PostDispatchInfo { actual_weight: None, pays_fee: pays_fee })
+			} else {
+				fail!(Error::<T>::AttestationNotFound);
 			}
 			/*
 			console.log('Submitting Proof Of Retrievability Attestation with ID:', attestationID)
@@ -599,6 +1182,153 @@ decl_module!{
 			handlers.forEach(handler => handler([PoR]))
 			*/
 		}
+
+		// ADMINISTRATION
+
+		#[weight = 100000] //todo weight
+		fn set_parameters(origin, parameters: PalletParameters<T::BlockNumber>){
+			ensure_root(origin)?;
+			ensure!(
+				parameters.challenge_delay >= One::one()
+					&& parameters.audit_period >= One::one()
+					&& parameters.attestors_per_challenge >= 1
+					&& parameters.hosters_per_plan >= 1,
+				Error::<T>::InvalidParameters
+			);
+			<Parameters<T>>::put(parameters.clone());
+			Self::deposit_event(RawEvent::ParametersSet(parameters));
+		}
+
+		#[weight = 100000] //todo weight
+		fn force_end_contract(origin, contract_id: T::ContractId){
+			ensure_root(origin)?;
+			let contract = <GetContractByID<T>>::get(&contract_id).ok_or(Error::<T>::ContractNotFound)?;
+			ensure!(contract.status != ContractStatus::Ended, Error::<T>::ContractEnded);
+			Self::end_contract(contract_id);
+			Self::deposit_event(RawEvent::ContractForceEnded(contract_id));
+			// keep the plan served if its contract was stuck
+			if <GetPlanByID<T>>::contains_key(&contract.plan) {
+				Self::make_new_contract(None, None, Some(contract.plan))?;
+			}
+		}
+
+		#[weight = 100000] //todo weight
+		fn force_remove_roles(origin, user_id: T::UserId){
+			ensure_root(origin)?;
+			ensure!(<GetUserByID<T>>::contains_key(&user_id), Error::<T>::UserNotFound);
+			let held : Vec<Role> = [Role::Encoder, Role::Hoster, Role::Attestor].iter()
+				.filter(|role| <Roles<T>>::get(role, user_id).is_some())
+				.cloned()
+				.collect();
+			for role in held.iter() {
+				<Roles<T>>::insert(role, user_id, RoleValue::None);
+				Self::deposit_event(RawEvent::RoleRemoved(user_id, role.clone()));
+			}
+			if held.contains(&Role::Hoster) && !<GetExitByHoster<T>>::contains_key(&user_id) {
+				Self::start_hoster_exit(user_id);
+			}
+			Self::deposit_event(RawEvent::UserRolesForceRemoved(user_id));
+		}
+
+		#[weight = 100000] //todo weight
+		fn delete_feed(origin, feed_id: T::FeedId){
+			ensure_root(origin)?;
+			ensure!(<GetFeedByID<T>>::contains_key(&feed_id), Error::<T>::FeedNotFound);
+			let plan_ids : Vec<T::PlanId> = <PlansByFeed<T>>::iter_prefix(&feed_id)
+				.map(|x| x.0)
+				.collect();
+			for plan_id in plan_ids {
+				if let Some(plan) = <GetPlanByID<T>>::get(&plan_id) {
+					Self::remove_plan(plan_id, plan);
+				}
+			}
+			<PlansByFeed<T>>::remove_prefix(&feed_id);
+			Self::remove_feed(feed_id);
+			Self::deposit_event(RawEvent::FeedDeleted(feed_id));
+		}
+
+		#[weight = 100000] //todo weight
+		fn force_resolve_challenge(origin, challenge_id: T::ChallengeId, passed: bool){
+			ensure_root(origin)?;
+			let challenge = <GetChallengeByID<T>>::get(&challenge_id).ok_or(Error::<T>::ChallengeNotFound)?;
+			ensure!(challenge.status == ChallengeStatus::Open, Error::<T>::ChallengeNotOpen);
+			let contract = <GetContractByID<T>>::get(&challenge.contract).ok_or(Error::<T>::ContractNotFound)?;
+			Self::resolve_challenge(challenge_id, challenge, contract.hoster, passed);
+			Self::deposit_event(RawEvent::ChallengeForceResolved(challenge_id, passed));
+		}
+
+		#[weight = 100000] //todo weight
+		fn force_resolve_attestation(origin, attestation_id: T::AttestationId, passed: bool){
+			ensure_root(origin)?;
+			let attestation = <GetAttestationByID<T>>::get(&attestation_id).ok_or(Error::<T>::AttestationNotFound)?;
+			ensure!(attestation.status == ChallengeStatus::Open, Error::<T>::AttestationNotOpen);
+			let hoster_option = <GetContractByID<T>>::get(&attestation.contract)
+				.map(|contract| contract.hoster);
+			Self::resolve_attestation(attestation_id, attestation, hoster_option, passed);
+			Self::deposit_event(RawEvent::AttestationForceResolved(attestation_id, passed));
+		}
+	}
+}
+
+/******************************************************************************
+  Runtime API
+******************************************************************************/
+impl<T: Trait> Module<T> {
+
+	/// Contracts the user takes part in as publisher, encoder or hoster
+	pub fn contracts_by_user(address: T::AccountId) -> Vec<Contract<T>> {
+		match <GetIDByUser<T>>::get(&address) {
+			Some(user_id) => {
+				let published = <GetPlanByID<T>>::iter()
+					.filter(|x| x.1.publisher == user_id)
+					.flat_map(|x| <ContractsByPlan<T>>::iter_prefix(&x.0));
+				let contract_ids : BTreeSet<T::ContractId> = <ContractsByHoster<T>>::iter_prefix(&user_id)
+					.chain(<ContractsByEncoder<T>>::iter_prefix(&user_id))
+					.chain(published)
+					.map(|x| x.0)
+					.collect();
+				contract_ids.iter()
+					.filter_map(|contract_id| <GetContractByID<T>>::get(contract_id))
+					.collect()
+			},
+			None => Vec::new(),
+		}
+	}
+
+	/// Challenges the hoster still has to answer
+	pub fn open_challenges_by_hoster(address: T::AccountId) -> Vec<Challenge<T>> {
+		match <GetIDByUser<T>>::get(&address) {
+			Some(user_id) => Self::challenges_of_hoster(&user_id)
+				.into_iter()
+				.map(|x| x.1)
+				.filter(|challenge| challenge.status == ChallengeStatus::Open)
+				.collect(),
+			None => Vec::new(),
+		}
+	}
+
+	/// Hosting plans published for the feed
+	pub fn plans_by_feed(feed_id: T::FeedId) -> Vec<Plan<T>> {
+		<PlansByFeed<T>>::iter_prefix(&feed_id)
+			.filter_map(|x| <GetPlanByID<T>>::get(&x.0))
+			.collect()
+	}
+
+	/// Users currently holding the role
+	pub fn active_providers(role: Role) -> Vec<User<T>> {
+		<Roles<T>>::iter_prefix(&role)
+			.filter(|x| x.1.is_some())
+			.filter_map(|x| <GetUserByID<T>>::get(&x.0))
+			.collect()
+	}
+
+	/// Plans with fewer hosters than the replication minimum
+	pub fn unmatched_plans() -> Vec<T::PlanId> {
+		let hosters_per_plan = Self::parameters().hosters_per_plan as usize;
+		<GetPlanByID<T>>::iter()
+			.map(|x| x.0)
+			.filter(|plan_id| Self::hosters_of_plan(plan_id).len() < hosters_per_plan)
+			.collect()
 	}
 }
 
@@ -607,7 +1337,23 @@ decl_module!{
 ******************************************************************************/
 impl<T: Trait> Module<T> {
 
-	fn reg_user(user_address: T::AccountId, noise_key: Option<NoiseKey>){
+	// hands out the current value of an id counter and advances it,
+	// failing instead of wrapping around once every id is taken
+	fn next_id<Counter, Id>() -> Result<Id, DispatchError>
+	where
+		Counter: StorageValue<Id, Query = Id>,
+		Id: FullCodec + Copy + CheckedAdd + One
+	{
+		Counter::try_mutate(|next|{
+			let id = *next;
+			*next = id.checked_add(&One::one()).ok_or(Error::<T>::IdsExhausted)?;
+			Ok(id)
+		})
+	}
+
+	// a new user reserves a deposit for its stored record unless registered at genesis,
+	// the record is never removed so the deposit stays held
+	fn reg_user(user_address: T::AccountId, noise_key: Option<NoiseKey>, with_deposit: bool) -> DispatchResult {
 		if let Some(user_id) = <GetIDByUser<T>>::get(&user_address){
 			if let Some(user) = <GetUserByID<T>>::get(&user_id){
 				<GetUserByID<T>>::insert(user_id, User::<T> {
@@ -618,75 +1364,1042 @@ impl<T: Trait> Module<T> {
 				// some err
 			}
 		} else {
-			let mut x = <GetNextUserID<T>>::get();
+			let x = <GetNextUserID<T>>::get();
 				let new_user = User {
 					id: x.clone(),
 					address: user_address.clone(),
 					noise_key: noise_key
 				};
+				// sized with a noise key, so registering one later stays covered
+				let deposit_size = User::<T> { noise_key: Some(NoiseKey::default()), ..new_user.clone() }.encoded_size();
+				if with_deposit {
+					ensure!(T::Currency::can_reserve(&user_address, Self::deposit_of(deposit_size)), Error::<T>::CannotReserve);
+				}
+				Self::next_id::<GetNextUserID<T>, _>()?;
+				if with_deposit {
+					Self::hold_deposit(&user_address, Deposited::User(x), deposit_size)?;
+				}
 				<GetUserByID<T>>::insert(x, new_user.clone());
 				<GetIDByUser<T>>::insert(&user_address, x.clone());
-			<GetNextUserID<T>>::put(x+One::one());
+			Self::deposit_event(RawEvent::NewUser(x, user_address));
+		}
+		Ok(())
+	}
+
+	// inserts the items of an exported state under their ids and rebuilds the lookups,
+	// ids are handed out after the highest restored one. Deposits, bonds, hoster exits
+	// and plan escrows are not restored, so restored plans do not pay their hosters
+	#[cfg(feature = "std")]
+	fn restore_state(state: &StateDump<T>){
+		fn after<Id: AtLeast32Bit + Copy>(ids: impl Iterator<Item = Id>) -> Id {
+			ids.max().map_or(Zero::zero(), |id| id.checked_add(&One::one())
+				.expect("restored ids leave room for a next id; qed"))
+		}
+		for user in state.users.iter() {
+			<GetUserByID<T>>::insert(user.id, user.clone());
+			<GetIDByUser<T>>::insert(&user.address, user.id);
+		}
+		for (role, user_id) in state.roles.iter() {
+			<Roles<T>>::insert(role, user_id, RoleValue::Some(0));
+		}
+		for (hoster_id, hoster_region) in state.hoster_regions.iter() {
+			<GetRegionByHoster<T>>::insert(hoster_id, hoster_region.clone());
+		}
+		for feed in state.feeds.iter() {
+			<GetFeedByID<T>>::insert(feed.id, feed.clone());
+		}
+		for plan in state.plans.iter() {
+			<GetPlanByID<T>>::insert(plan.id, Plan::<T> { payment: None, ..plan.clone() });
+			<PlansByFeed<T>>::insert(plan.feed, plan.id, ());
+		}
+		for contract in state.contracts.iter() {
+			<GetContractByID<T>>::insert(contract.id, contract.clone());
+			<ContractsByHoster<T>>::insert(contract.hoster, contract.id, ());
+			<ContractsByEncoder<T>>::insert(contract.encoder, contract.id, ());
+			<ContractsByPlan<T>>::insert(contract.plan, contract.id, ());
+			match contract.status {
+				ContractStatus::Hosting | ContractStatus::Retiring =>
+					<HostingContracts<T>>::mutate(|contracts| contracts.push(contract.id)),
+				ContractStatus::Ended => Self::schedule_pruning(Prunable::Contract(contract.id)),
+				ContractStatus::Encoding => (),
+			}
+		}
+		for challenge in state.challenges.iter() {
+			<GetChallengeByID<T>>::insert(challenge.id, challenge.clone());
+			<ChallengesByContract<T>>::insert(challenge.contract, challenge.id, ());
+			match challenge.status {
+				ChallengeStatus::Open => <ChallengesByDeadline<T>>::insert(challenge.deadline, challenge.id, ()),
+				_ => Self::schedule_pruning(Prunable::Challenge(challenge.id)),
+			}
+		}
+		for attestation in state.attestations.iter() {
+			<GetAttestationByID<T>>::insert(attestation.id, attestation.clone());
+			if attestation.status != ChallengeStatus::Open {
+				Self::schedule_pruning(Prunable::Attestation(attestation.id));
+			}
+		}
+		<GetNextUserID<T>>::put(after(state.users.iter().map(|user| user.id)));
+		<GetNextFeedID<T>>::put(after(state.feeds.iter().map(|feed| feed.id)));
+		<GetNextPlanID<T>>::put(after(state.plans.iter().map(|plan| plan.id)));
+		<GetNextContractID<T>>::put(after(state.contracts.iter().map(|contract| contract.id)));
+		<GetNextChallengeID<T>>::put(after(state.challenges.iter().map(|challenge| challenge.id)));
+		<GetNextAttestationID<T>>::put(after(state.attestations.iter().map(|attestation| attestation.id)));
+	}
+
+	/// Checks a feed's merkle root has no more roots than `MaxRootChildren`
+	pub fn check_tree_root(meta: &TreeRoot) -> Result<(), Error<T>> {
+		ensure!(meta.children.len() <= T::MaxRootChildren::get() as usize, Error::<T>::TooManyRootChildren);
+		Ok(())
+	}
+
+	/// Sorts the ranges of a plan and merges overlapping and adjacent ones,
+	/// ranges are inclusive and have to lie within the feed's chunks
+	pub fn normalize_ranges(mut ranges: Ranges<ChunkIndex>, chunk_count: u64) -> Result<Ranges<ChunkIndex>, Error<T>> {
+		ensure!(!ranges.is_empty(), Error::<T>::NoRanges);
+		ensure!(ranges.len() <= T::MaxRanges::get() as usize, Error::<T>::TooManyRanges);
+		ensure!(ranges.iter().all(|range| range.0 <= range.1), Error::<T>::InvertedRange);
+		ensure!(ranges.iter().all(|range| range.1 < chunk_count), Error::<T>::RangeBeyondFeed);
+		ranges.sort();
+		let mut merged : Ranges<ChunkIndex> = Vec::with_capacity(ranges.len());
+		for range in ranges {
+			match merged.last_mut() {
+				Some(last) if range.0 <= last.1.saturating_add(1) => last.1 = last.1.max(range.1),
+				_ => merged.push(range),
+			}
 		}
+		Ok(merged)
+	}
+
+	fn parameters() -> PalletParameters<T::BlockNumber> {
+		<Parameters<T>>::get().unwrap_or_else(|| PalletParameters {
+			challenge_delay: T::ChallengeDelay::get(),
+			audit_period: T::AuditPeriod::get(),
+			default_audits_per_period: T::DefaultAuditsPerPeriod::get(),
+			attestors_per_challenge: T::AttestorsPerChallenge::get(),
+			hosters_per_plan: T::HostersPerPlan::get()
+		})
+	}
+
+	// the depositor pays for the stored feed and plan and escrows the hosting payment,
+	// genesis feeds are free
+	fn insert_feed_and_plan(
+		depositor: Option<&T::AccountId>,
+		publisher_id: T::UserId,
+		publickey: FeedKey,
+		meta: TreeRoot,
+		ranges: Ranges<ChunkIndex>,
+		min_regions: u8,
+		payment: Option<PlanPayment<T::AssetId, AssetBalanceOf<T>>>
+	) -> Result<T::PlanId, DispatchError> {
+		//TODO -- currently re-registering existing feeds creates a new FeedID, should lookup first.
+		// dispatches are not transactional, so everything that can fail is checked before the first write
+		// every hoster covers a single region
+		ensure!(min_regions as u32 <= Self::parameters().hosters_per_plan, Error::<T>::TooManyRegions);
+		let feed_id = <GetNextFeedID<T>>::get();
+		let plan_id = <GetNextPlanID<T>>::get();
+		// the plan is matched right away, with a new contract for each missing replica
+		let contract_ids = <GetNextContractID<T>>::get()
+			.checked_add(&T::ContractId::from(Self::parameters().hosters_per_plan));
+		ensure!(
+			feed_id < T::FeedId::max_value() && plan_id < T::PlanId::max_value() && contract_ids.is_some(),
+			Error::<T>::IdsExhausted
+		);
+		let feed = Feed::<T> {
+			id: feed_id.clone(),
+			publickey: publickey,
+			meta: meta
+		};
+		let plan = Plan::<T> {
+			id: plan_id.clone(),
+			publisher: publisher_id,
+			feed: feed_id,
+			ranges: ranges,
+			min_regions: min_regions,
+			payment: payment
+		};
+		if let Some(who) = depositor {
+			let deposit = Self::deposit_of(feed.encoded_size()).saturating_add(Self::deposit_of(plan.encoded_size()));
+			ensure!(T::Currency::can_reserve(who, deposit), Error::<T>::CannotReserve);
+			// the escrow is the only reserve that can still fail, so it goes first
+			if let Some(payment) = &plan.payment {
+				<generic_asset::Module<T>>::reserve(&payment.asset, who, payment.escrow)?;
+			}
+		}
+		Self::next_id::<GetNextFeedID<T>, _>()?;
+		Self::next_id::<GetNextPlanID<T>, _>()?;
+		if let Some(who) = depositor {
+			Self::hold_deposit(who, Deposited::Feed(feed_id), feed.encoded_size())?;
+			Self::hold_deposit(who, Deposited::Plan(plan_id), plan.encoded_size())?;
+		}
+		<GetFeedByID<T>>::insert(feed_id, feed);
+		<GetPlanByID<T>>::insert(plan_id, plan);
+		<PlansByFeed<T>>::insert(feed_id, plan_id, ());
+		Self::deposit_event(RawEvent::NewFeed(feed_id, publickey));
+		Self::deposit_event(RawEvent::NewPlan(plan_id.clone(), feed_id, publisher_id));
+		Ok(plan_id)
+	}
+
+	fn deposit_of(encoded_size: usize) -> BalanceOf<T> {
+		let size : u32 = encoded_size.saturated_into();
+		T::DepositPerByte::get().saturating_mul(size.into())
+	}
+
+	fn hold_deposit(who: &T::AccountId, item: Deposited<T>, encoded_size: usize) -> DispatchResult {
+		let amount = Self::deposit_of(encoded_size);
+		T::Currency::reserve(who, amount)?;
+		<GetDepositByItem<T>>::insert(item, (who.clone(), amount));
+		Ok(())
+	}
+
+	fn release_deposit(item: Deposited<T>){
+		if let Some((who, amount)) = <GetDepositByItem<T>>::take(item){
+			T::Currency::unreserve(&who, amount);
+		}
+	}
+
+	fn remove_feed(feed_id: T::FeedId){
+		<GetFeedByID<T>>::remove(&feed_id);
+		Self::release_deposit(Deposited::Feed(feed_id));
+	}
+
+	fn address_of(user_id: &T::UserId) -> T::AccountId {
+		<GetUserByID<T>>::get(user_id)
+			.map(|user| user.address)
+			.unwrap_or_default()
+	}
+
+	// hosters encoding or hosting the plan, retiring hosters are not counted
+	fn hosters_of_plan(plan_id: &T::PlanId) -> BTreeSet<T::UserId> {
+		<ContractsByPlan<T>>::iter_prefix(plan_id)
+			.filter_map(|x| <GetContractByID<T>>::get(&x.0))
+			.filter(|contract| contract.status == ContractStatus::Encoding || contract.status == ContractStatus::Hosting)
+			.map(|contract| contract.hoster)
+			.collect()
+	}
+
+	fn feed_key_of_plan(plan_id: &T::PlanId) -> FeedKey {
+		<GetPlanByID<T>>::get(plan_id)
+			.and_then(|plan| <GetFeedByID<T>>::get(&plan.feed))
+			.map(|feed| feed.publickey)
+			.unwrap_or_default()
 	}
 
 	fn make_new_contract(
 		encoder_option: Option<T::UserId>,
 		hoster_option: Option<T::UserId>,
 		plan_option: Option<T::PlanId>
-	){
-		let mut contract_id : T::ContractId;
+	) -> DispatchResult {
 		let mut random_hoster_option = None;
 		let mut random_encoder_option = None;
 		let mut random_plan_option = None;
 		match (encoder_option, hoster_option, plan_option) {
 			(Some(encoder_id), Some(hoster_id), Some(plan_id)) => {
-				let x = <GetNextContractID<T>>::get();
-					if let Some(plan) = <GetPlanByID<T>>::get(plan_id){
-						let new_contract = Contract::<T> {
-							id: x.clone(),
-							plan: plan_id,
-							ranges: plan.ranges,
-							encoder: encoder_id,
-							hoster: hoster_id
-						};
-						contract_id = x.clone();
-						<GetContractByID<T>>::insert(x, new_contract.clone());
-						<GetNextContractID<T>>::put(x+One::one());
-						Self::deposit_event(RawEvent::NewContract(contract_id.clone()));
-					};
+				Self::insert_contract(encoder_id, hoster_id, plan_id)?;
 			},
 			(None, None, Some(plan_id)) => {  // Condition: if planID && encoders available & hosters available
-				random_hoster_option = Self::get_random_of_role(&[], &Role::Hoster, 1).pop();
-				random_encoder_option = Self::get_random_of_role(&[], &Role::Encoder, 1).pop();
-				if random_encoder_option.is_some() && random_hoster_option.is_some(){
-					Self::make_new_contract(random_encoder_option, random_hoster_option, plan_option);
+				// tops the plan up to the replication minimum with hosters not holding it yet,
+				// one at a time so every pick sees the regions covered by the previous ones
+				let missing = Self::parameters().hosters_per_plan
+					.saturating_sub(Self::hosters_of_plan(&plan_id).len() as u32);
+				for _ in 0..missing {
+					let hosting = Self::hosters_of_plan(&plan_id);
+					random_hoster_option = Self::get_random_of_role_filtered(&[], &Role::Hoster, 1, |hoster_id, role_value|{
+						role_value.is_some() && !hosting.contains(&hoster_id) && Self::hoster_fits_plan(&plan_id, &hoster_id)
+					}).pop();
+					random_encoder_option = Self::get_random_of_role(&[], &Role::Encoder, 1).pop();
+					if random_encoder_option.is_none() || random_hoster_option.is_none() {
+						break;
+					}
+					Self::make_new_contract(random_encoder_option, random_hoster_option, plan_option)?;
 				}
 			},
 			(None, Some(hoster_id), None) => { //Condition: if hosterID && encoders available & plans available
 				random_encoder_option = Self::get_random_of_role(&[], &Role::Encoder, 1).pop();
-				let plans : Vec<T::PlanId> = <GetPlanByID<T>>::iter().map(|x|x.0).collect();
+				let plans : Vec<T::PlanId> = <GetPlanByID<T>>::iter()
+					.map(|x|x.0)
+					.filter(|plan_id| Self::hoster_fits_plan(plan_id, &hoster_id))
+					.collect();
 				random_plan_option = Self::get_random_of_vec(&[], plans, 1).pop();
 				if random_encoder_option.is_some() && random_plan_option.is_some(){
-					Self::make_new_contract(random_encoder_option, hoster_option, random_plan_option);
+					Self::make_new_contract(random_encoder_option, hoster_option, random_plan_option)?;
 				}
 			},
 			(Some(encoder_id), None, None) => { //Condition: if encoderID && hosters available & plans available
-				random_hoster_option = Self::get_random_of_role(&[], &Role::Hoster, 1).pop();
 				let plans : Vec<T::PlanId> = <GetPlanByID<T>>::iter().map(|x|x.0).collect();
 				random_plan_option = Self::get_random_of_vec(&[], plans, 1).pop();
+				if let Some(plan_id) = random_plan_option {
+					random_hoster_option = Self::get_random_of_role_filtered(&[], &Role::Hoster, 1, |hoster_id, role_value|{
+						role_value.is_some() && Self::hoster_fits_plan(&plan_id, &hoster_id)
+					}).pop();
+				}
 				if random_hoster_option.is_some() && random_plan_option.is_some(){
-					Self::make_new_contract(encoder_option, random_hoster_option, random_plan_option);
+					Self::make_new_contract(encoder_option, random_hoster_option, random_plan_option)?;
 				}
 			},
 			(_, _, _) => ()
 		}
+		Ok(())
+	}
+
+	fn insert_contract(
+		encoder_id: T::UserId,
+		hoster_id: T::UserId,
+		plan_id: T::PlanId
+	) -> Result<Option<T::ContractId>, DispatchError> {
+		if let Some(plan) = <GetPlanByID<T>>::get(plan_id){
+			let x = Self::next_id::<GetNextContractID<T>, _>()?;
+			let new_contract = Contract::<T> {
+				id: x.clone(),
+				plan: plan_id,
+				ranges: plan.ranges,
+				encoder: encoder_id,
+				hoster: hoster_id,
+				status: ContractStatus::Encoding
+			};
+			<GetContractByID<T>>::insert(x, new_contract.clone());
+			<ContractsByHoster<T>>::insert(hoster_id, x, ());
+			<ContractsByEncoder<T>>::insert(encoder_id, x, ());
+			<ContractsByPlan<T>>::insert(plan_id, x, ());
+			Self::deposit_event(RawEvent::NewContract(
+				x.clone(),
+				plan_id,
+				Self::feed_key_of_plan(&plan_id),
+				encoder_id,
+				Self::address_of(&encoder_id),
+				hoster_id,
+				Self::address_of(&hoster_id),
+				new_contract.ranges
+			));
+			Ok(Some(x))
+		} else {
+			Ok(None)
+		}
+	}
+
+	// finds a new encoder and hoster for the plan of a contract whose hoster is leaving
+	fn start_handover(contract_id: T::ContractId) -> bool {
+		if let Some(mut contract) = <GetContractByID<T>>::get(&contract_id){
+			let plan_id = contract.plan;
+			// the replacement takes over the retiring replica, so it goes to a hoster not holding
+			// one yet and only has to cover the regions the other replicas leave out
+			let hosting = Self::hosters_of_plan(&plan_id);
+			let covered = Self::regions_of_plan_without(&plan_id, Some(&contract_id));
+			let hoster_option = Self::get_random_of_role_filtered(&[], &Role::Hoster, 1, |hoster_id, role_value|{
+				role_value.is_some()
+					&& !hosting.contains(&hoster_id)
+					&& Self::hoster_fits_regions(&plan_id, &hoster_id, &covered)
+			}).pop();
+			let encoder_option = Self::get_random_of_role(&[], &Role::Encoder, 1).pop();
+			if let (Some(encoder_id), Some(hoster_id)) = (encoder_option, hoster_option) {
+				// without a free contract id the handover is retried in a later block
+				if let Ok(Some(replacement_id)) = Self::insert_contract(encoder_id, hoster_id, plan_id){
+					contract.status = ContractStatus::Retiring;
+					<GetContractByID<T>>::insert(contract_id, contract);
+					<GetHandoverByContract<T>>::insert(replacement_id, contract_id);
+					Self::deposit_event(RawEvent::HandoverStarted(contract_id, replacement_id));
+					return true;
+				}
+			}
+		}
+		false
+	}
+
+	// returns a retiring contract to the status it had before its handover started
+	fn cancel_handover(retiring_id: T::ContractId){
+		if let Some(mut retiring) = <GetContractByID<T>>::get(&retiring_id){
+			if retiring.status == ContractStatus::Retiring {
+				retiring.status = if Self::hosting_contracts().contains(&retiring_id) {
+					ContractStatus::Hosting
+				} else {
+					ContractStatus::Encoding
+				};
+				<GetContractByID<T>>::insert(retiring_id, retiring);
+			}
+		}
+	}
+
+	// ends every contract retired by the given replacement, following chains of
+	// replacements whose own hosters left before they started hosting
+	fn complete_handover(replacement_id: T::ContractId){
+		let mut current_id = replacement_id;
+		while let Some(retiring_id) = <GetHandoverByContract<T>>::take(&current_id){
+			// root may have ended the retiring contract in the meantime
+			if let Some(mut retiring) = <GetContractByID<T>>::get(&retiring_id).filter(|contract| contract.status != ContractStatus::Ended){
+				let hoster_id = retiring.hoster;
+				retiring.status = ContractStatus::Ended;
+				<GetContractByID<T>>::insert(retiring_id, retiring);
+				<HostingContracts<T>>::mutate(|contracts| contracts.retain(|id| id != &retiring_id));
+				Self::schedule_pruning(Prunable::Contract(retiring_id));
+				<GetExitByHoster<T>>::mutate(&hoster_id, |exit_option|{
+					if let Some(exit) = exit_option {
+						exit.pending.retain(|id| id != &retiring_id);
+					}
+				});
+				Self::deposit_event(RawEvent::HandoverCompleted(retiring_id, replacement_id));
+				Self::try_finish_exit(hoster_id, <system::Module<T>>::block_number());
+			}
+			current_id = retiring_id;
+		}
+	}
+
+	// hands over the active contracts of a hoster that lost its role, its bond is
+	// released once all of them are taken over and the notice period is over
+	fn start_hoster_exit(user_id: T::UserId){
+		let active : Vec<T::ContractId> = <ContractsByHoster<T>>::iter_prefix(&user_id)
+			.map(|x| x.0)
+			.filter(|contract_id|{
+				<GetContractByID<T>>::get(contract_id)
+					.map(|contract| contract.status != ContractStatus::Ended)
+					.unwrap_or(false)
+			})
+			.collect();
+		for contract_id in active.iter() {
+			Self::start_handover(*contract_id);
+		}
+		let notice_ends = <system::Module<T>>::block_number() + T::HosterNoticePeriod::get();
+		<GetExitByHoster<T>>::insert(user_id, HosterExit::<T> {
+			notice_ends: notice_ends,
+			pending: active
+		});
+		Self::deposit_event(RawEvent::HosterExitStarted(user_id, notice_ends));
+	}
+
+	fn end_contract(contract_id: T::ContractId){
+		if let Some(mut contract) = <GetContractByID<T>>::get(&contract_id){
+			if contract.status == ContractStatus::Ended {
+				return;
+			}
+			contract.status = ContractStatus::Ended;
+			<GetContractByID<T>>::insert(contract_id, contract.clone());
+			<HostingContracts<T>>::mutate(|contracts| contracts.retain(|id| id != &contract_id));
+			// a replacement ending before it hosts gives the retiring contract back
+			// to its leaving hoster, whose exit retries the handover
+			if let Some(retiring_id) = <GetHandoverByContract<T>>::take(&contract_id) {
+				Self::cancel_handover(retiring_id);
+			}
+			<GetExitByHoster<T>>::mutate(&contract.hoster, |exit_option|{
+				if let Some(exit) = exit_option {
+					exit.pending.retain(|id| id != &contract_id);
+				}
+			});
+			Self::try_finish_exit(contract.hoster, <system::Module<T>>::block_number());
+			Self::schedule_pruning(Prunable::Contract(contract_id));
+		}
+	}
+
+	// ends all contracts of the plan and removes it, and its feed once no plan is left
+	fn remove_plan(plan_id: T::PlanId, plan: Plan<T>){
+		let contract_ids : Vec<T::ContractId> = <ContractsByPlan<T>>::iter_prefix(&plan_id)
+			.map(|x| x.0)
+			.collect();
+		for contract_id in contract_ids {
+			Self::end_contract(contract_id);
+		}
+		<GetAuditRateByPlan<T>>::remove(&plan_id);
+		<GetPlanByID<T>>::remove(&plan_id);
+		Self::release_deposit(Deposited::Plan(plan_id));
+		if let Some(payment) = plan.payment {
+			<generic_asset::Module<T>>::unreserve(&payment.asset, &Self::address_of(&plan.publisher), payment.escrow);
+		}
+		<PlansByFeed<T>>::remove(&plan.feed, &plan_id);
+		if <PlansByFeed<T>>::iter_prefix(&plan.feed).next().is_none() {
+			Self::remove_feed(plan.feed);
+		}
+	}
+
+	fn resolve_challenge(challenge_id: T::ChallengeId, mut challenge: Challenge<T>, hoster_id: T::UserId, passed: bool){
+		<ChallengesByDeadline<T>>::remove(&challenge.deadline, &challenge_id);
+		<GetRecordByHoster<T>>::mutate(&hoster_id, |record|{
+			if passed {
+				record.proofs_passed = record.proofs_passed.saturating_add(1);
+			} else {
+				record.proofs_failed = record.proofs_failed.saturating_add(1);
+			}
+		});
+		Self::schedule_pruning(Prunable::Challenge(challenge_id));
+		if passed {
+			Self::pay_hoster(challenge.contract, hoster_id);
+			challenge.status = ChallengeStatus::Passed;
+			Self::deposit_event(RawEvent::ProofOfStorageConfirmed(challenge_id.clone(), hoster_id));
+		} else {
+			challenge.status = ChallengeStatus::Failed;
+			Self::deposit_event(RawEvent::ProofOfStorageFailed(challenge_id.clone(), hoster_id));
+		}
+		<GetChallengeByID<T>>::insert(challenge_id, challenge);
+	}
+
+	// pays the hoster of a contract for a passed proof out of the escrow of its plan,
+	// as long as anything is left in it
+	fn pay_hoster(contract_id: T::ContractId, hoster_id: T::UserId){
+		let plan_id = match <GetContractByID<T>>::get(&contract_id) {
+			Some(contract) => contract.plan,
+			None => return,
+		};
+		<GetPlanByID<T>>::mutate(&plan_id, |plan_option|{
+			if let Some(plan) = plan_option {
+				if let Some(payment) = &mut plan.payment {
+					let amount = payment.per_proof.min(payment.escrow);
+					if amount.is_zero() {
+						return;
+					}
+					let missing = <generic_asset::Module<T>>::repatriate_reserved(
+						&payment.asset,
+						&Self::address_of(&plan.publisher),
+						&Self::address_of(&hoster_id),
+						amount
+					);
+					let paid = amount.saturating_sub(missing);
+					payment.escrow = payment.escrow.saturating_sub(paid);
+					Self::deposit_event(RawEvent::HosterPaid(contract_id, hoster_id, payment.asset, paid));
+				}
+			}
+		});
+	}
+
+	fn resolve_attestation(
+		attestation_id: T::AttestationId,
+		mut attestation: Attestation<T>,
+		hoster_option: Option<T::UserId>,
+		passed: bool
+	){
+		if let Some(hoster_id) = hoster_option {
+			<GetRecordByHoster<T>>::mutate(&hoster_id, |record|{
+				if passed {
+					record.attestations_passed = record.attestations_passed.saturating_add(1);
+				} else {
+					record.attestations_failed = record.attestations_failed.saturating_add(1);
+				}
+			});
+		}
+		Self::schedule_pruning(Prunable::Attestation(attestation_id));
+		let hoster_id = hoster_option.unwrap_or_default();
+		if passed {
+			attestation.status = ChallengeStatus::Passed;
+			Self::deposit_event(RawEvent::AttestationReportConfirmed(attestation_id.clone(), hoster_id));
+		} else {
+			attestation.status = ChallengeStatus::Failed;
+			Self::deposit_event(RawEvent::AttestationReportFailed(attestation_id.clone(), hoster_id));
+		}
+		<GetAttestationByID<T>>::insert(attestation_id, attestation);
+	}
+
+	fn try_finish_exit(hoster_id: T::UserId, now: T::BlockNumber){
+		if let Some(exit) = <GetExitByHoster<T>>::get(&hoster_id){
+			if exit.pending.is_empty() && now >= exit.notice_ends {
+				<GetExitByHoster<T>>::remove(&hoster_id);
+				if let Some(bond) = <GetBondByHoster<T>>::take(&hoster_id){
+					if let Some(user) = <GetUserByID<T>>::get(&hoster_id){
+						T::Currency::unreserve(&user.address, bond);
+					}
+				}
+				Self::deposit_event(RawEvent::HosterExited(hoster_id));
+			}
+		}
+	}
+
+	// retries handovers no replacement was found for yet, at most `MaxHandoversPerBlock`
+	// per block, and releases the bonds of hosters whose notice period is over
+	fn process_hoster_exits(now: T::BlockNumber) -> Weight {
+		let exits : Vec<(T::UserId, HosterExit<T>)> = <GetExitByHoster<T>>::iter().collect();
+		let mut reads : Weight = exits.len() as Weight;
+		let mut writes : Weight = 0;
+		if exits.is_empty() {
+			return T::DbWeight::get().reads(reads);
+		}
+		// a handover scans all hosters, checking each against the hosters and regions of the
+		// plan's contracts, and all encoders
+		let hosters = <Roles<T>>::iter_prefix(&Role::Hoster).count() as Weight;
+		let encoders = <Roles<T>>::iter_prefix(&Role::Encoder).count() as Weight;
+		reads += hosters + encoders;
+		let mut retries_left = T::MaxHandoversPerBlock::get();
+		for (hoster_id, exit) in exits {
+			for contract_id in exit.pending.iter() {
+				if retries_left == 0 {
+					break;
+				}
+				reads += 1;
+				let unmatched = <GetContractByID<T>>::get(contract_id)
+					.filter(|contract| contract.status != ContractStatus::Retiring);
+				if let Some(contract) = unmatched {
+					retries_left -= 1;
+					let plan_contracts = <ContractsByPlan<T>>::iter_prefix(&contract.plan).count() as Weight;
+					// the retiring contract, plan contracts with their hosters and their regions,
+					// then per hoster its role, the plan and its own region, then the encoders
+					// and the reads of inserting the replacement
+					reads += 1
+						+ 5 * plan_contracts
+						+ 3 * hosters
+						+ encoders
+						+ 7;
+					if Self::start_handover(*contract_id) {
+						// replacement, its indexes, the id counter, the nonce,
+						// the retiring contract and the handover
+						writes += 8;
+					}
+				}
+			}
+			Self::try_finish_exit(hoster_id, now);
+			reads += 3;
+			writes += 3;
+		}
+		T::DbWeight::get().reads_writes(reads, writes)
+	}
+
+	// the depositor pays for the stored challenge, audits are free. only a hoster holding
+	// the chunks can answer, so contracts still encoding or already ended are not challenged
+	fn issue_challenge(
+		depositor: Option<&T::AccountId>,
+		contract: Contract<T>
+	) -> Result<T::ChallengeId, DispatchError> {
+		ensure!(
+			contract.status == ContractStatus::Hosting || contract.status == ContractStatus::Retiring,
+			Error::<T>::ContractNotHosting
+		);
+		let contract_id = contract.id;
+		// dispatches are not transactional, so everything that can fail is checked before the first write
+		let challenge_id = <GetNextChallengeID<T>>::get();
+		ensure!(challenge_id < T::ChallengeId::max_value(), Error::<T>::IdsExhausted);
+		let random_chunks = Self::random_from_ranges(contract.ranges);
+		let deadline = <system::Module<T>>::block_number() + Self::parameters().challenge_delay;
+		let challenge = Challenge::<T> {
+			id: challenge_id.clone(),
+			contract: contract_id,
+			chunks: random_chunks,
+			deadline: deadline,
+			status: ChallengeStatus::Open
+		};
+		if let Some(who) = depositor {
+			let deposit = Self::deposit_of(challenge.encoded_size());
+			ensure!(T::Currency::can_reserve(who, deposit), Error::<T>::CannotReserve);
+		}
+		Self::next_id::<GetNextChallengeID<T>, _>()?;
+		if let Some(who) = depositor {
+			Self::hold_deposit(who, Deposited::Challenge(challenge_id), challenge.encoded_size())?;
+		}
+		<GetChallengeByID<T>>::insert(challenge_id, challenge.clone());
+		<ChallengesByContract<T>>::insert(contract_id, challenge_id, ());
+		<ChallengesByDeadline<T>>::insert(deadline, challenge_id, ());
+		Self::deposit_event(RawEvent::NewProofOfStorageChallenge(
+			challenge_id.clone(),
+			contract_id,
+			contract.hoster,
+			Self::address_of(&contract.hoster),
+			Self::feed_key_of_plan(&contract.plan),
+			challenge.chunks,
+			deadline
+		));
+		Ok(challenge_id)
+	}
+
+	// fails every challenge whose deadline was the previous block
+	fn expire_challenges(now: T::BlockNumber) -> Weight {
+		let deadline = now.saturating_sub(One::one());
+		let expired : Vec<T::ChallengeId> = <ChallengesByDeadline<T>>::iter_prefix(&deadline)
+			.map(|x| x.0)
+			.collect();
+		<ChallengesByDeadline<T>>::remove_prefix(&deadline);
+		for challenge_id in expired.iter() {
+			let contract_option = <GetChallengeByID<T>>::mutate(challenge_id, |challenge_option|{
+				challenge_option.as_mut().map(|challenge|{
+					challenge.status = ChallengeStatus::Failed;
+					challenge.contract
+				})
+			});
+			let hoster_option = contract_option
+				.and_then(|contract_id| <GetContractByID<T>>::get(&contract_id))
+				.map(|contract| contract.hoster);
+			if let Some(hoster_id) = hoster_option {
+				<GetRecordByHoster<T>>::mutate(&hoster_id, |record|{
+					record.proofs_failed = record.proofs_failed.saturating_add(1);
+				});
+			}
+			if contract_option.is_some() {
+				Self::schedule_pruning(Prunable::Challenge(*challenge_id));
+			}
+			let hoster_id = hoster_option.unwrap_or_default();
+			Self::deposit_event(RawEvent::ProofOfStorageFailed(challenge_id.clone(), hoster_id));
+		}
+		let count = expired.len() as Weight;
+		T::DbWeight::get().reads_writes(1 + 4 * count, 1 + 4 * count)
+	}
+
+	fn schedule_pruning(item: Prunable<T>){
+		let prune_at = <system::Module<T>>::block_number() + T::ResolvedRetention::get();
+		let tail = PruneQueueTail::get();
+		<PruneQueue<T>>::insert(tail, (prune_at, item));
+		PruneQueueTail::put(tail + 1);
+	}
+
+	// removes resolved items whose retention window is over, oldest first,
+	// at most `MaxPrunedPerBlock` per block
+	fn prune_resolved(now: T::BlockNumber) -> Weight {
+		let mut head = PruneQueueHead::get();
+		let tail = PruneQueueTail::get();
+		let mut reads : Weight = 2;
+		let mut writes : Weight = 0;
+		let mut pruned : u32 = 0;
+		while head < tail && pruned < T::MaxPrunedPerBlock::get() {
+			reads += 1;
+			match <PruneQueue<T>>::get(head) {
+				Some((prune_at, _)) if prune_at > now => break,
+				Some((_, item)) => {
+					let (item_reads, item_writes) = Self::prune(item);
+					reads += item_reads;
+					writes += item_writes;
+				},
+				None => {}
+			}
+			<PruneQueue<T>>::remove(head);
+			writes += 1;
+			head += 1;
+			pruned += 1;
+		}
+		if pruned > 0 {
+			PruneQueueHead::put(head);
+			writes += 1;
+		}
+		T::DbWeight::get().reads_writes(reads, writes)
+	}
+
+	// returns the reads and writes it took
+	fn prune(item: Prunable<T>) -> (Weight, Weight) {
+		match item {
+			Prunable::Challenge(challenge_id) => {
+				if let Some(challenge) = <GetChallengeByID<T>>::take(&challenge_id){
+					<ChallengesByContract<T>>::remove(&challenge.contract, &challenge_id);
+					<ChallengesByDeadline<T>>::remove(&challenge.deadline, &challenge_id);
+				}
+				Self::release_deposit(Deposited::Challenge(challenge_id));
+				(3, 5)
+			},
+			Prunable::Attestation(attestation_id) => {
+				<GetAttestationByID<T>>::remove(&attestation_id);
+				Self::release_deposit(Deposited::Attestation(attestation_id));
+				(2, 3)
+			},
+			Prunable::Contract(contract_id) => {
+				if let Some(contract) = <GetContractByID<T>>::take(&contract_id){
+					<ContractsByHoster<T>>::remove(&contract.hoster, &contract_id);
+					<ContractsByEncoder<T>>::remove(&contract.encoder, &contract_id);
+					<ContractsByPlan<T>>::remove(&contract.plan, &contract_id);
+				}
+				(1, 4)
+			},
+		}
+	}
+
+	// challenges each hosting contract with a probability of its audit rate per audit period,
+	// starting at a random contract and stopping once the weight budget is used up
+	fn issue_audits() -> Weight {
+		let db = T::DbWeight::get();
+		let budget = T::AuditWeightBudget::get();
+		// contract, audit rate, then the challenge counter, parameters, plan, feed and hoster read
+		// and the challenge counter, challenge and its indexes written by the challenge
+		let audit_cost = db.reads_writes(7, 4);
+		let contracts = <HostingContracts<T>>::get();
+		// the list of hosted contracts is decoded as a whole, charged as a read per contract
+		let mut used = db.reads(1 + contracts.len() as Weight);
+		if contracts.is_empty() {
+			return used;
+		}
+		let parameters = Self::parameters();
+		let period = parameters.audit_period.saturated_into::<u32>().max(1);
+		let seed = (Self::unique_nonce(), T::Randomness::random(b"audits"))
+			.using_encoded(|b| <[u8; 32]>::decode(&mut TrailingZeroInput::new(b)))
+			.expect("input is padded with zeroes; qed");
+		let mut rng = ChaChaRng::from_seed(seed);
+		let start = Self::pick_usize(&mut rng, contracts.len() - 1);
+		for contract_id in contracts[start..].iter().chain(contracts[..start].iter()) {
+			if used.saturating_add(audit_cost) > budget {
+				break;
+			}
+			used = used.saturating_add(db.reads(2));
+			if let Some(contract) = <GetContractByID<T>>::get(contract_id){
+				let audits = <GetAuditRateByPlan<T>>::get(&contract.plan)
+					.unwrap_or(parameters.default_audits_per_period);
+				if rng.next_u32() % period < audits {
+					used = used.saturating_add(db.reads_writes(5, 4));
+					if Self::issue_challenge(None, contract).is_err() {
+						native::warn!("challenge ids exhausted, skipping audits");
+						break;
+					}
+				}
+			}
+		}
+		used
+	}
+
+	// a hoster fits a plan if the plan already spans enough regions,
+	// or if the hoster would add a region the plan does not cover yet.
+	fn hoster_fits_plan(plan_id: &T::PlanId, hoster_id: &T::UserId) -> bool {
+		Self::hoster_fits_regions(plan_id, hoster_id, &Self::regions_of_plan(plan_id))
+	}
+
+	// whether a hoster can hold a replica of a plan whose other replicas cover the regions
+	fn hoster_fits_regions(plan_id: &T::PlanId, hoster_id: &T::UserId, covered: &BTreeSet<Region>) -> bool {
+		if let Some(plan) = <GetPlanByID<T>>::get(plan_id){
+			if covered.len() >= plan.min_regions as usize {
+				true
+			} else {
+				match Self::effective_region(hoster_id) {
+					Some(region) => !covered.contains(&region),
+					None => false
+				}
+			}
+		} else {
+			false
+		}
+	}
+
+	fn regions_of_plan(plan_id: &T::PlanId) -> BTreeSet<Region> {
+		Self::regions_of_plan_without(plan_id, None)
+	}
+
+	// regions of the plan's replicas, leaving out the given contract
+	fn regions_of_plan_without(plan_id: &T::PlanId, except: Option<&T::ContractId>) -> BTreeSet<Region> {
+		<ContractsByPlan<T>>::iter_prefix(plan_id)
+			.filter(|x| Some(&x.0) != except)
+			.filter_map(|x| <GetContractByID<T>>::get(&x.0))
+			.filter(|contract| contract.status == ContractStatus::Encoding || contract.status == ContractStatus::Hosting)
+			.filter_map(|contract| Self::effective_region(&contract.hoster))
+			.collect()
+	}
+
+	fn challenges_of_hoster(hoster_id: &T::UserId) -> Vec<(T::ChallengeId, Challenge<T>)> {
+		<ContractsByHoster<T>>::iter_prefix(hoster_id)
+			.flat_map(|x| <ChallengesByContract<T>>::iter_prefix(&x.0))
+			.filter_map(|x| <GetChallengeByID<T>>::get(&x.0).map(|challenge| (x.0, challenge)))
+			.collect()
+	}
+
+	// declared region of a hoster, unless attestors contradicted it more often than they confirmed it
+	fn effective_region(hoster_id: &T::UserId) -> Option<Region> {
+		<GetRegionByHoster<T>>::get(hoster_id).and_then(|hoster_region|{
+			if hoster_region.contradictions > hoster_region.confirmations {
+				None
+			} else {
+				Some(hoster_region.region)
+			}
+		})
+	}
+
+	// a chunk served quickly to an attestor places the hoster in the attestor's region,
+	// a slow one only tells that the hoster is somewhere else
+	fn record_region_report(hoster_id: T::UserId, attestor_region: Region, latency: u8){
+		if let Some(mut hoster_region) = <GetRegionByHoster<T>>::get(&hoster_id){
+			let nearby = latency <= NEARBY_LATENCY;
+			let claimed = hoster_region.region == attestor_region;
+			if nearby && claimed {
+				hoster_region.confirmations = hoster_region.confirmations.saturating_add(1);
+				Self::deposit_event(RawEvent::HosterRegionConfirmed(hoster_id, attestor_region));
+			} else if nearby || claimed {
+				hoster_region.contradictions = hoster_region.contradictions.saturating_add(1);
+				Self::deposit_event(RawEvent::HosterRegionContradicted(hoster_id, attestor_region));
+			} else {
+				return;
+			}
+			<GetRegionByHoster<T>>::insert(hoster_id, hoster_region);
+		}
+	}
+
+	/// Local storage key a hoster keeps a chunk of a feed under, written by the
+	/// hoster's dat tooling (e.g. with the `offchain_localStorageSet` RPC).
+	pub fn chunk_storage_key(feed_key: &FeedKey, chunk: ChunkIndex) -> Vec<u8> {
+		let mut key = b"datdot::chunk::".to_vec();
+		key.extend_from_slice(feed_key.as_ref());
+		key.extend_from_slice(&chunk.to_le_bytes());
+		key
+	}
+
+	// accounts of the keys in the local keystore, with the key to sign for them
+	fn local_accounts() -> Vec<(T::AccountId, T::Public)> {
+		<T::AuthorityId as AppCrypto<T::Public, T::Signature>>::RuntimeAppPublic::all()
+			.into_iter()
+			.map(|key|{
+				let generic_public = <T::AuthorityId as AppCrypto<T::Public, T::Signature>>::GenericPublic::from(key);
+				let public : T::Public = generic_public.into();
+				(public.clone().into_account(), public)
+			})
+			.collect()
+	}
+
+	// registered users whose key is in the local keystore
+	fn local_users() -> Vec<(T::UserId, T::Public)> {
+		Self::local_accounts()
+			.into_iter()
+			.filter_map(|(account, public)| <GetIDByUser<T>>::get(&account).map(|user_id| (user_id, public)))
+			.collect()
+	}
+
+	// offchain: answer open challenges of hosters whose key is in the local keystore
+	fn answer_challenges(){
+		let local_hosters = Self::local_users();
+		if local_hosters.is_empty() {
+			return;
+		}
+		let open_challenges = local_hosters.iter()
+			.flat_map(|x| Self::challenges_of_hoster(&x.0))
+			.filter(|x| x.1.status == ChallengeStatus::Open);
+		for (challenge_id, challenge) in open_challenges {
+			let contract = match <GetContractByID<T>>::get(&challenge.contract) {
+				Some(contract) => contract,
+				None => continue,
+			};
+			let public = match local_hosters.iter().find(|x| x.0 == contract.hoster) {
+				Some(hoster) => hoster.1.clone(),
+				None => continue,
+			};
+			// a proof already sent from this node is waiting to be included
+			let answered = StorageValueRef::persistent(&(b"datdot::answered::", challenge_id).encode());
+			if let Some(Some(true)) = answered.get::<bool>() {
+				continue;
+			}
+			let feed_option = <GetPlanByID<T>>::get(&contract.plan)
+				.and_then(|plan| <GetFeedByID<T>>::get(&plan.feed));
+			let proofs_option = feed_option.and_then(|feed|{
+				challenge.chunks.iter()
+					.map(|chunk|{
+						sp_io::offchain::local_storage_get(
+							StorageKind::PERSISTENT,
+							&Self::chunk_storage_key(&feed.publickey, *chunk)
+						).map(|data| Public::from_raw(blake2_256(&data)))
+					})
+					.collect::<Option<Vec<Proof>>>()
+			});
+			if let Some(proofs) = proofs_option {
+				let results = Signer::<T, T::AuthorityId>::all_accounts()
+					.with_filter(vec![public])
+					.send_signed_transaction(|_account| Call::submit_proof_of_storage(challenge_id, proofs.clone()));
+				for (_account, result) in results.iter() {
+					match result {
+						Ok(()) => answered.set(&true),
+						Err(()) => native::warn!("failed to submit proof for challenge {:?}", challenge_id),
+					}
+				}
+			} else {
+				native::warn!("missing local chunks to answer challenge {:?}", challenge_id);
+			}
+		}
+	}
+
+	// offchain: retrieve the chunks of open attestations assigned to a local attestor
+	// from the hoster and report whether and how fast they were served
+	fn attest_retrievability(){
+		let local_attestors = Self::local_users();
+		if local_attestors.is_empty() {
+			return;
+		}
+		let node_endpoint = sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, b"datdot::hoster_endpoint")
+			.unwrap_or(DEFAULT_HOSTER_ENDPOINT.to_vec());
+		// region this node measures latencies from, set by its operator
+		let attestor_region = sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, b"datdot::attestor_region")
+			.and_then(|region| Region::decode(&mut &region[..]).ok());
+		let open_attestations = <GetAttestationByID<T>>::iter()
+			.filter(|x| x.1.status == ChallengeStatus::Open);
+		for (attestation_id, attestation) in open_attestations {
+			let public = match local_attestors.iter().find(|x| x.0 == attestation.attestor) {
+				Some(attestor) => attestor.1.clone(),
+				None => continue,
+			};
+			let reported = StorageValueRef::persistent(&(b"datdot::reported::", attestation_id).encode());
+			if let Some(Some(true)) = reported.get::<bool>() {
+				continue;
+			}
+			let contract = match <GetContractByID<T>>::get(&attestation.contract) {
+				Some(contract) => contract,
+				None => continue,
+			};
+			let feed_option = <GetPlanByID<T>>::get(&contract.plan)
+				.and_then(|plan| <GetFeedByID<T>>::get(&plan.feed));
+			let feed = match feed_option {
+				Some(feed) => feed,
+				None => continue,
+			};
+			let endpoint = Self::hoster_endpoint(&contract.hoster).unwrap_or_else(|| node_endpoint.clone());
+			let reports : Vec<Report> = attestation.chunks.iter()
+				.map(|chunk| Self::fetch_chunk(&endpoint, &feed.publickey, *chunk, attestor_region))
+				.collect();
+			let results = Signer::<T, T::AuthorityId>::all_accounts()
+				.with_filter(vec![public])
+				.send_signed_transaction(|_account| Call::submit_attestation_report(attestation_id, reports.clone()));
+			for (_account, result) in results.iter() {
+				match result {
+					Ok(()) => reported.set(&true),
+					Err(()) => native::warn!("failed to submit report for attestation {:?}", attestation_id),
+				}
+			}
+		}
+	}
+
+	/// Local storage key of the base URL an attestor fetches the chunks of a hoster from
+	pub fn hoster_endpoint_key(hoster_id: &T::UserId) -> Vec<u8> {
+		(b"datdot::hoster_endpoint::", hoster_id).encode()
+	}
+
+	fn hoster_endpoint(hoster_id: &T::UserId) -> Option<Vec<u8>> {
+		sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &Self::hoster_endpoint_key(hoster_id))
+	}
+
+	// GET <endpoint>/<feed key as hex>/<chunk index>, the hoster answers with the chunk
+	fn fetch_chunk(endpoint: &[u8], feed_key: &FeedKey, chunk: ChunkIndex, attestor_region: Option<Region>) -> Report {
+		let not_served = Report { location: attestor_region, latency: None };
+		let url = Self::chunk_url(endpoint, feed_key, chunk);
+		let url = match sp_std::str::from_utf8(&url) {
+			Ok(url) => url,
+			Err(_) => return not_served,
+		};
+		let start = sp_io::offchain::timestamp();
+		let deadline = start.add(Duration::from_millis(CHUNK_REQUEST_TIMEOUT));
+		let response_option = http::Request::get(url)
+			.deadline(deadline)
+			.send()
+			.ok()
+			.and_then(|pending| pending.try_wait(deadline).ok())
+			.and_then(|response| response.ok())
+			.filter(|response| response.code == 200);
+		match response_option {
+			Some(response) => {
+				let latency = sp_io::offchain::timestamp().diff(&start).millis();
+				let body : Vec<u8> = response.body().collect();
+				if body.is_empty() {
+					not_served
+				} else {
+					Report {
+						location: attestor_region,
+						latency: Some(latency.min(u8::max_value() as u64) as u8)
+					}
+				}
+			},
+			None => not_served,
+		}
+	}
+
+	fn chunk_url(endpoint: &[u8], feed_key: &FeedKey, chunk: ChunkIndex) -> Vec<u8> {
+		const HEX: &[u8; 16] = b"0123456789abcdef";
+		let mut url = endpoint.to_vec();
+		url.push(b'/');
+		for byte in feed_key.as_ref() {
+			url.push(HEX[(byte >> 4) as usize]);
+			url.push(HEX[(byte & 0xf) as usize]);
+		}
+		url.push(b'/');
+		let mut digits = Vec::new();
+		let mut rest = chunk;
+		loop {
+			digits.push(b'0' + (rest % 10) as u8);
+			rest /= 10;
+			if rest == 0 {
+				break;
+			}
+		}
+		url.extend(digits.iter().rev());
+		url
 	}
 
 	fn random_from_ranges(ranges: Ranges<ChunkIndex>) -> Vec<ChunkIndex>{
 		//TODO, currently only returns first chunk of every available range,
 		//should return some random selection.
-		ranges.iter().map(|x|x.0).collect()
+		ranges.iter().map(|x|x.0).take(T::MaxChallengeChunks::get() as usize).collect()
 	}
 
 	fn validate_proof(proof: Proof, challenge: Challenge<T>) -> bool {
@@ -699,16 +2412,6 @@ impl<T: Trait> Module<T> {
 
 		(rng.next_u32() % (max as u32 + 1)) as usize
 	}
-
-
-	/// Pick an item at pseudo-random from the slice, given the `rng`. `None` iff the slice is empty.
-	fn pick_item<'a, R: RngCore, E>(rng: &mut R, items: &'a [E]) -> Option<&'a E> {
-		if items.is_empty() {
-			None
-		} else {
-			Some(&items[Self::pick_usize(rng, items.len() - 1)])
-		}
-	}
 	// ---
 
 	fn unique_nonce() -> u64 {
@@ -717,21 +2420,23 @@ impl<T: Trait> Module<T> {
 		nonce
 	}
 
-	fn get_random_of_vec<Item: Copy>(influence: &[u8], members: Vec<Item>, count: u32) -> Vec<Item>{
+	// picks `count` distinct members, or all of them if there are not more
+	fn get_random_of_vec<Item: Copy>(influence: &[u8], mut members: Vec<Item>, count: u32) -> Vec<Item>{
 		let match_count : usize = count.try_into().unwrap();
 		if members.len() <= match_count { members } else {
 			let nonce : u64 = Self::unique_nonce();
-			let mut random_select: Vec<Item> = Vec::new();
 				// added indeces to seed in order to ensure challenges Get unique randomness.
 			let seed = (nonce, T::Randomness::random(influence))
 				.using_encoded(|b| <[u8; 32]>::decode(&mut TrailingZeroInput::new(b)))
 				.expect("input is padded with zeroes; qed");
 			let mut rng = ChaChaRng::from_seed(seed);
-			let pick_item = |_| Self::pick_item(&mut rng, &members[..]).expect("exited if members empty; qed");
-			for item in (0..count).map(pick_item){
-				random_select.push(*item);
+			// partial Fisher-Yates shuffle, the picks end up in front
+			for i in 0..match_count {
+				let j = i + Self::pick_usize(&mut rng, members.len() - 1 - i);
+				members.swap(i, j);
 			}
-			random_select
+			members.truncate(match_count);
+			members
 		}
 	}
 
@@ -748,9 +2453,9 @@ impl<T: Trait> Module<T> {
 
 
 	fn get_random_of_role_filtered<F>(influence: &[u8], role: &Role, count: u32, filter: F) -> Vec<T::UserId>
-	where F: Fn(RoleValue) -> bool {
+	where F: Fn(T::UserId, RoleValue) -> bool {
 		let members : Vec<T::UserId> = <Roles<T>>::iter_prefix(role).filter_map(|x|{
-			if filter(x.1){
+			if filter(x.0, x.1){
 				Some(x.0)
 			} else {
 				None
@@ -0,0 +1,180 @@
+// Storage migrations run from `on_runtime_upgrade`.
+//
+// Every change to the encoding of a stored type bumps `Releases` and adds a
+// step to `migrate` that translates the values written by the previous release.
+
+use super::*;
+use sp_std::cell::Cell;
+
+#[derive(Decode, PartialEq, Eq, Encode, Clone, Copy, RuntimeDebug)]
+pub enum Releases {
+	// formats before storage was versioned
+	V1_0_0,
+	// plan regions, contract and challenge status, attestation chunks, secondary indexes
+	V2_0_0,
+	// plan hosting payments
+	V3_0_0,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V1_0_0
+	}
+}
+
+/// Release written at genesis and reached after all migrations ran
+pub const CURRENT_RELEASE: Releases = Releases::V3_0_0;
+
+// types as they were encoded by V1_0_0
+pub(crate) mod v1 {
+	use super::*;
+
+	#[derive(Decode, Encode)]
+	pub struct Plan<T: Trait> {
+		pub id: T::PlanId,
+		pub feed: T::FeedId,
+		pub publisher: T::UserId,
+		pub ranges: Ranges<ChunkIndex>
+	}
+
+	#[derive(Decode, Encode)]
+	pub struct Contract<T: Trait> {
+		pub id: T::ContractId,
+		pub plan: T::PlanId,
+		pub ranges: Ranges<ChunkIndex>,
+		pub encoder: T::UserId,
+		pub hoster: T::UserId
+	}
+
+	#[derive(Decode, Encode)]
+	pub struct Challenge<T: Trait> {
+		pub id: T::ChallengeId,
+		pub contract: T::ContractId,
+		pub chunks: Vec<ChunkIndex>
+	}
+
+	#[derive(Decode, Encode)]
+	pub struct Attestation<T: Trait> {
+		pub id: T::AttestationId,
+		pub attestor: T::UserId,
+		pub contract: T::ContractId
+	}
+}
+
+// types as they were encoded by V2_0_0
+pub(crate) mod v2 {
+	use super::*;
+
+	#[derive(Decode, Encode)]
+	pub struct Plan<T: Trait> {
+		pub id: T::PlanId,
+		pub feed: T::FeedId,
+		pub publisher: T::UserId,
+		pub ranges: Ranges<ChunkIndex>,
+		pub min_regions: u8
+	}
+}
+
+pub fn migrate<T: Trait>() -> Weight {
+	let mut weight : Weight = T::DbWeight::get().reads(1);
+	if StorageVersion::get() == Releases::V1_0_0 {
+		weight = weight.saturating_add(migrate_to_v2::<T>());
+		StorageVersion::put(Releases::V2_0_0);
+		weight = weight.saturating_add(T::DbWeight::get().writes(1));
+	}
+	if StorageVersion::get() == Releases::V2_0_0 {
+		weight = weight.saturating_add(migrate_to_v3::<T>());
+		StorageVersion::put(Releases::V3_0_0);
+		weight = weight.saturating_add(T::DbWeight::get().reads_writes(1, 1));
+	}
+	weight
+}
+
+// adds the fields introduced since V1_0_0 and builds the secondary indexes for existing entries
+fn migrate_to_v2<T: Trait>() -> Weight {
+	let now = <system::Module<T>>::block_number();
+	let reads = Cell::new(0 as Weight);
+	let writes = Cell::new(0 as Weight);
+	let count = |r: Weight, w: Weight|{
+		reads.set(reads.get() + r);
+		writes.set(writes.get() + w);
+	};
+
+	<GetPlanByID<T>>::translate(|plan_id, old: v1::Plan<T>|{
+		count(1, 2);
+		<PlansByFeed<T>>::insert(old.feed, plan_id, ());
+		Some(v2::Plan::<T> {
+			id: old.id,
+			feed: old.feed,
+			publisher: old.publisher,
+			ranges: old.ranges,
+			// no region requirement, as before
+			min_regions: 0
+		})
+	});
+
+	// whether hosting started was never stored, treat every contract as hosting so it gets audited
+	<GetContractByID<T>>::translate(|contract_id, old: v1::Contract<T>|{
+		count(1, 5);
+		<ContractsByHoster<T>>::insert(old.hoster, contract_id, ());
+		<ContractsByEncoder<T>>::insert(old.encoder, contract_id, ());
+		<ContractsByPlan<T>>::insert(old.plan, contract_id, ());
+		<HostingContracts<T>>::mutate(|contracts| contracts.push(contract_id));
+		Some(Contract::<T> {
+			id: old.id,
+			plan: old.plan,
+			ranges: old.ranges,
+			encoder: old.encoder,
+			hoster: old.hoster,
+			status: ContractStatus::Hosting
+		})
+	});
+
+	// challenges without an answer get a fresh deadline instead of failing at once
+	let deadline = now + Module::<T>::parameters().challenge_delay;
+	<GetChallengeByID<T>>::translate(|challenge_id, old: v1::Challenge<T>|{
+		count(1, 3);
+		<ChallengesByContract<T>>::insert(old.contract, challenge_id, ());
+		<ChallengesByDeadline<T>>::insert(deadline, challenge_id, ());
+		Some(Challenge::<T> {
+			id: old.id,
+			contract: old.contract,
+			chunks: old.chunks,
+			deadline: deadline,
+			status: ChallengeStatus::Open
+		})
+	});
+
+	<GetAttestationByID<T>>::translate(|_attestation_id, old: v1::Attestation<T>|{
+		count(2, 1);
+		let chunks = <GetContractByID<T>>::get(&old.contract)
+			.map(|contract| Module::<T>::random_from_ranges(contract.ranges))
+			.unwrap_or_default();
+		Some(Attestation::<T> {
+			id: old.id,
+			attestor: old.attestor,
+			contract: old.contract,
+			chunks: chunks,
+			status: ChallengeStatus::Open
+		})
+	});
+
+	T::DbWeight::get().reads_writes(reads.get(), writes.get())
+}
+
+// plans published before hosting payments pay nothing
+fn migrate_to_v3<T: Trait>() -> Weight {
+	let count = Cell::new(0 as Weight);
+	<GetPlanByID<T>>::translate(|_plan_id, old: v2::Plan<T>|{
+		count.set(count.get() + 1);
+		Some(Plan::<T> {
+			id: old.id,
+			feed: old.feed,
+			publisher: old.publisher,
+			ranges: old.ranges,
+			min_regions: old.min_regions,
+			payment: None
+		})
+	});
+	T::DbWeight::get().reads_writes(count.get(), count.get())
+}
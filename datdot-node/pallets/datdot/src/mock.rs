@@ -0,0 +1,277 @@
+// Test runtime of the pallet: balances pay deposits and bonds, a generic asset pays
+// hosting plans, and offchain transactions are signed with `crypto::DatdotAuthId`.
+
+use crate::*;
+use frame_support::{
+	impl_outer_event, impl_outer_origin, parameter_types, traits::OnInitialize, weights::{RuntimeDbWeight, Weight},
+};
+use sp_core::H256;
+use sp_runtime::{
+	testing::{Header, TestXt},
+	traits::{BlakeTwo256, Hash as HashT, IdentityLookup},
+	AccountId32, MultiSignature, MultiSigner, Perbill,
+};
+
+impl_outer_origin! {
+	pub enum Origin for Test where system = frame_system {}
+}
+
+mod datdot {
+	pub use crate::Event;
+}
+
+impl_outer_event! {
+	pub enum TestEvent for Test {
+		frame_system<T>,
+		pallet_balances<T>,
+		generic_asset<T>,
+		datdot<T>,
+	}
+}
+
+pub type AccountId = AccountId32;
+pub type Balance = u64;
+pub type Extrinsic = TestXt<Call<Test>, ()>;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Test;
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+	pub const DbWeight: RuntimeDbWeight = RuntimeDbWeight { read: 1, write: 1 };
+}
+
+impl system::Trait for Test {
+	type BaseCallFilter = ();
+	type Origin = Origin;
+	type Call = ();
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = AccountId;
+	type Lookup = IdentityLookup<AccountId>;
+	type Header = Header;
+	type Event = TestEvent;
+	type BlockHashCount = BlockHashCount;
+	type MaximumBlockWeight = MaximumBlockWeight;
+	type DbWeight = DbWeight;
+	type BlockExecutionWeight = ();
+	type ExtrinsicBaseWeight = ();
+	type MaximumExtrinsicWeight = MaximumBlockWeight;
+	type MaximumBlockLength = MaximumBlockLength;
+	type AvailableBlockRatio = AvailableBlockRatio;
+	type Version = ();
+	type ModuleToIndex = ();
+	type AccountData = pallet_balances::AccountData<Balance>;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+}
+
+parameter_types! {
+	pub const ExistentialDeposit: Balance = 1;
+}
+
+impl pallet_balances::Trait for Test {
+	type Balance = Balance;
+	type Event = TestEvent;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+}
+
+impl generic_asset::Trait for Test {
+	type Balance = Balance;
+	type AssetId = u32;
+	type Event = TestEvent;
+}
+
+impl frame_system::offchain::SigningTypes for Test {
+	type Public = MultiSigner;
+	type Signature = MultiSignature;
+}
+
+impl<LocalCall> frame_system::offchain::SendTransactionTypes<LocalCall> for Test
+where
+	Call<Test>: From<LocalCall>,
+{
+	type OverarchingCall = Call<Test>;
+	type Extrinsic = Extrinsic;
+}
+
+impl<LocalCall> frame_system::offchain::CreateSignedTransaction<LocalCall> for Test
+where
+	Call<Test>: From<LocalCall>,
+{
+	fn create_transaction<C: AppCrypto<Self::Public, Self::Signature>>(
+		call: Call<Test>,
+		_public: MultiSigner,
+		_account: AccountId,
+		nonce: u64,
+	) -> Option<(Call<Test>, <Extrinsic as sp_runtime::traits::Extrinsic>::SignaturePayload)> {
+		Some((call, (nonce, ())))
+	}
+}
+
+// deterministic, so tests only depend on the nonce mixed into every seed
+pub struct TestRandomness;
+
+impl Randomness<H256> for TestRandomness {
+	fn random(subject: &[u8]) -> H256 {
+		BlakeTwo256::hash(subject)
+	}
+}
+
+parameter_types! {
+	pub const HosterBond: Balance = 1_000;
+	pub const HosterNoticePeriod: u64 = 10;
+	pub const MaxHandoversPerBlock: u32 = 2;
+	pub const ChallengeDelay: u64 = 5;
+	pub const AuditPeriod: u64 = 100;
+	pub const DefaultAuditsPerPeriod: u32 = 0;
+	pub const AttestorsPerChallenge: u32 = 1;
+	pub const HostersPerPlan: u32 = 1;
+	pub const AuditWeightBudget: Weight = 1_000_000;
+	pub const ResolvedRetention: u64 = 10;
+	pub const MaxPrunedPerBlock: u32 = 50;
+	pub const MaxRanges: u32 = 8;
+	pub const MaxRootChildren: u32 = 8;
+	pub const MaxChallengeChunks: u32 = 8;
+	pub const DepositPerByte: Balance = 1;
+}
+
+impl Trait for Test {
+	type Event = TestEvent;
+	type AuthorityId = crypto::DatdotAuthId;
+	type Hash = H256;
+	type Randomness = TestRandomness;
+	type Currency = Balances;
+	type HosterBond = HosterBond;
+	type HosterNoticePeriod = HosterNoticePeriod;
+	type MaxHandoversPerBlock = MaxHandoversPerBlock;
+	type ChallengeDelay = ChallengeDelay;
+	type AuditPeriod = AuditPeriod;
+	type DefaultAuditsPerPeriod = DefaultAuditsPerPeriod;
+	type AttestorsPerChallenge = AttestorsPerChallenge;
+	type HostersPerPlan = HostersPerPlan;
+	type AuditWeightBudget = AuditWeightBudget;
+	type ResolvedRetention = ResolvedRetention;
+	type MaxPrunedPerBlock = MaxPrunedPerBlock;
+	type MaxRanges = MaxRanges;
+	type MaxRootChildren = MaxRootChildren;
+	type MaxChallengeChunks = MaxChallengeChunks;
+	type DepositPerByte = DepositPerByte;
+	type FeedId = u32;
+	type UserId = u32;
+	type ContractId = u32;
+	type ChallengeId = u32;
+	type PlanId = u32;
+	type AttestationId = u32;
+}
+
+pub type System = system::Module<Test>;
+pub type Balances = pallet_balances::Module<Test>;
+pub type GenericAsset = generic_asset::Module<Test>;
+pub type DatVerify = Module<Test>;
+
+/// Generic asset plans are paid in
+pub const HOSTING_ASSET: u32 = 16;
+/// Balance of every endowed account, in balances and in the hosting asset
+pub const ENDOWMENT: Balance = 1_000_000;
+
+pub fn account(seed: u8) -> AccountId {
+	AccountId::from([seed; 32])
+}
+
+pub fn noise_key(seed: u8) -> Public {
+	Public::from_raw([seed; 32])
+}
+
+/// Accounts `1` to `10` are endowed, block 1 is being built
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut storage = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	let endowed : Vec<AccountId> = (1..=10).map(account).collect();
+	pallet_balances::GenesisConfig::<Test> {
+		balances: endowed.iter().cloned().map(|who| (who, ENDOWMENT)).collect(),
+	}.assimilate_storage(&mut storage).unwrap();
+	generic_asset::GenesisConfig::<Test> {
+		assets: vec![HOSTING_ASSET],
+		initial_balance: ENDOWMENT,
+		endowed_accounts: endowed,
+		next_asset_id: 100,
+		staking_asset_id: 0,
+		spending_asset_id: 0,
+	}.assimilate_storage(&mut storage).unwrap();
+	GenesisConfig::<Test>::default().assimilate_storage(&mut storage).unwrap();
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
+
+/// Registers the account as user holding the roles, hosting in the region
+pub fn register(seed: u8, roles: &[Role], region: Region) -> u32 {
+	let origin = || Origin::signed(account(seed));
+	if <GetIDByUser<Test>>::get(account(seed)).is_none() {
+		DatVerify::new_user(origin()).unwrap();
+	}
+	for role in roles {
+		match role {
+			Role::Encoder => DatVerify::register_encoder(origin(), noise_key(seed)).unwrap(),
+			Role::Hoster => DatVerify::register_hoster(origin(), noise_key(seed), region).unwrap(),
+			Role::Attestor => DatVerify::register_attestor(origin()).unwrap(),
+		}
+	}
+	<GetIDByUser<Test>>::get(account(seed)).unwrap()
+}
+
+/// Merkle root of a feed of `chunks` chunks under a single root, distinct for every seed
+pub fn merkle_root(seed: u8, chunks: u64) -> (Public, TreeHashPayload, H512) {
+	(
+		Public::from_raw([seed; 32]),
+		TreeHashPayload {
+			hash_type: 2,
+			children: vec![ParentHashInRoot {
+				hash: H256::repeat_byte(seed),
+				// rightmost leaf of the feed
+				hash_number: 2 * (chunks - 1),
+				total_length: chunks * 1024
+			}]
+		},
+		H512::zero(),
+	)
+}
+
+/// Publishes a feed of `chunks` chunks with a plan for all of them
+pub fn publish(seed: u8, publisher: u8, chunks: u64) -> u32 {
+	DatVerify::publish_feed_and_plan(
+		Origin::signed(account(publisher)),
+		merkle_root(seed, chunks),
+		vec![(0, chunks - 1)],
+		0,
+		None,
+	).unwrap();
+	<GetNextPlanID<Test>>::get() - 1
+}
+
+pub fn datdot_events() -> Vec<RawEvent<
+	AccountId, u32, u32, u32, u32, u32, u32, u64, u32, Balance
+>> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			TestEvent::datdot(event) => Some(event),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Runs `on_initialize` of the blocks up to and including `n`
+pub fn run_to_block(n: u64) {
+	while System::block_number() < n {
+		System::set_block_number(System::block_number() + 1);
+		DatVerify::on_initialize(System::block_number());
+	}
+}
@@ -0,0 +1,911 @@
+use crate::*;
+use crate::mock::*;
+use frame_support::{assert_noop, assert_ok};
+
+fn normalize(ranges: Ranges<ChunkIndex>, chunk_count: u64) -> Result<Ranges<ChunkIndex>, DispatchError> {
+	DatVerify::normalize_ranges(ranges, chunk_count).map_err(DispatchError::from)
+}
+
+#[test]
+fn height_of_any_index_is_bounded() {
+	assert_eq!(Node::get_height(0), 0);
+	assert_eq!(Node::get_height(1), 1);
+	assert_eq!(Node::get_height(7), 3);
+	assert_eq!(Node::get_height(u64::max_value() - 1), 0);
+	assert_eq!(Node::get_height(u64::max_value()), 64);
+	assert_eq!(Node::leaves_up_to(u64::max_value()), 1 << 63);
+}
+
+#[test]
+fn chunk_count_follows_rightmost_root() {
+	let (_, payload, signature) = merkle_root(1, 4);
+	let mut meta = TreeRoot { signature, hash_type: payload.hash_type, children: payload.children };
+	assert_eq!(meta.chunk_count(), 4);
+	meta.children[0].hash_number = 3;
+	assert_eq!(meta.chunk_count(), 4);
+	meta.children[0].hash_number = u64::max_value();
+	assert_eq!(meta.chunk_count(), 1 << 63);
+	meta.children.clear();
+	assert_eq!(meta.chunk_count(), 0);
+}
+
+#[test]
+fn publish_accepts_root_at_highest_index() {
+	new_test_ext().execute_with(|| {
+		register(1, &[], 0);
+		let (key, mut payload, signature) = merkle_root(1, 1);
+		payload.children[0].hash_number = u64::max_value();
+		assert_ok!(DatVerify::publish_feed_and_plan(
+			Origin::signed(account(1)),
+			(key, payload, signature),
+			vec![(0, 3)],
+			0,
+			None
+		));
+		assert_eq!(<GetPlanByID<Test>>::get(0).unwrap().ranges, vec![(0, 3)]);
+	});
+}
+
+#[test]
+fn normalize_ranges_merges_overlapping_and_adjacent() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(normalize(vec![(4, 6), (0, 2)], 10), Ok(vec![(0, 2), (4, 6)]));
+		assert_eq!(normalize(vec![(2, 5), (0, 3)], 10), Ok(vec![(0, 5)]));
+		assert_eq!(normalize(vec![(0, 2), (3, 4), (6, 6)], 10), Ok(vec![(0, 4), (6, 6)]));
+		assert_eq!(normalize(vec![(1, 8), (2, 3)], 10), Ok(vec![(1, 8)]));
+		assert_eq!(normalize(vec![(9, 9)], 10), Ok(vec![(9, 9)]));
+	});
+}
+
+#[test]
+fn normalize_ranges_rejects_invalid_ranges() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(normalize(vec![], 10), Err(Error::<Test>::NoRanges.into()));
+		assert_eq!(normalize(vec![(3, 2)], 10), Err(Error::<Test>::InvertedRange.into()));
+		assert_eq!(normalize(vec![(0, 10)], 10), Err(Error::<Test>::RangeBeyondFeed.into()));
+		assert_eq!(normalize(vec![(0, 0)], 0), Err(Error::<Test>::RangeBeyondFeed.into()));
+		let too_many = (0..=MaxRanges::get() as u64).map(|i| (2 * i, 2 * i)).collect();
+		assert_eq!(normalize(too_many, 100), Err(Error::<Test>::TooManyRanges.into()));
+	});
+}
+
+#[test]
+fn publish_rejects_ranges_beyond_feed() {
+	new_test_ext().execute_with(|| {
+		register(1, &[], 0);
+		assert_noop!(
+			DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(1, 4), vec![(2, 4)], 0, None),
+			Error::<Test>::RangeBeyondFeed
+		);
+	});
+}
+
+// storage of a chain whose genesis publishes a feed of 4 chunks by account 1 with the ranges
+fn genesis_feed_storage(ranges: Ranges<ChunkIndex>) -> Result<sp_runtime::Storage, String> {
+	let (publickey, payload, signature) = merkle_root(1, 4);
+	let meta = TreeRoot { signature, hash_type: payload.hash_type, children: payload.children };
+	GenesisConfig::<Test> {
+		users: vec![(account(1), None, vec![], 0)],
+		feeds: vec![(publickey, meta, account(1), ranges, 0)],
+		state: None,
+	}.build_storage()
+}
+
+#[test]
+fn tree_roots_are_bounded() {
+	let (_, payload, signature) = merkle_root(1, 4);
+	let mut meta = TreeRoot { signature, hash_type: payload.hash_type, children: payload.children };
+	assert_eq!(DatVerify::check_tree_root(&meta).map_err(DispatchError::from), Ok(()));
+	meta.children = vec![meta.children[0].clone(); MaxRootChildren::get() as usize + 1];
+	assert_eq!(
+		DatVerify::check_tree_root(&meta).map_err(DispatchError::from),
+		Err(Error::<Test>::TooManyRootChildren.into())
+	);
+}
+
+#[test]
+fn genesis_feed_keeps_root_and_normalized_ranges() {
+	let storage = genesis_feed_storage(vec![(2, 3), (0, 1)]).unwrap();
+	sp_io::TestExternalities::new(storage).execute_with(|| {
+		let feed = <GetFeedByID<Test>>::get(0).unwrap();
+		assert_eq!(feed.meta.chunk_count(), 4);
+		assert_eq!(feed.meta.children[0].hash, H256::repeat_byte(1));
+		assert_eq!(<GetPlanByID<Test>>::get(0).unwrap().ranges, vec![(0, 3)]);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Invalid ranges of genesis feed")]
+fn genesis_feed_rejects_ranges_beyond_feed() {
+	let _ = genesis_feed_storage(vec![(0, 4)]);
+}
+
+#[test]
+#[should_panic(expected = "Invalid ranges of genesis feed")]
+fn genesis_feed_rejects_inverted_ranges() {
+	let _ = genesis_feed_storage(vec![(3, 2)]);
+}
+
+#[test]
+fn unregistering_needs_the_role() {
+	new_test_ext().execute_with(|| {
+		register(1, &[], 0);
+		let origin = || Origin::signed(account(1));
+		assert_noop!(DatVerify::unregister_encoder(origin()), Error::<Test>::NotEncoder);
+		assert_noop!(DatVerify::unregister_hoster(origin()), Error::<Test>::NotHoster);
+		assert_noop!(DatVerify::unregister_attestor(origin()), Error::<Test>::NotRegisteredAttestor);
+		register(1, &[Role::Encoder, Role::Attestor], 0);
+		assert_ok!(DatVerify::unregister_encoder(origin()));
+		assert_ok!(DatVerify::unregister_attestor(origin()));
+		assert_noop!(DatVerify::unregister_encoder(origin()), Error::<Test>::NotEncoder);
+		assert_noop!(DatVerify::unregister_attestor(origin()), Error::<Test>::NotRegisteredAttestor);
+	});
+}
+
+#[test]
+fn role_changes_emit_events() {
+	new_test_ext().execute_with(|| {
+		let user_id = register(1, &[Role::Encoder, Role::Attestor], 0);
+		assert_ok!(DatVerify::unregister_attestor(Origin::signed(account(1))));
+		assert_eq!(datdot_events(), vec![
+			RawEvent::NewUser(user_id, account(1)),
+			RawEvent::RoleRegistered(user_id, Role::Encoder),
+			RawEvent::RoleRegistered(user_id, Role::Attestor),
+			RawEvent::RoleRemoved(user_id, Role::Attestor),
+		]);
+	});
+}
+
+#[test]
+fn new_user_holds_deposit() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(DatVerify::new_user(Origin::signed(account(1))));
+		let (owner, amount) = <GetDepositByItem<Test>>::get(Deposited::User(0)).unwrap();
+		assert_eq!(owner, account(1));
+		assert!(amount > 0);
+		assert_eq!(Balances::reserved_balance(account(1)), amount);
+	});
+}
+
+#[test]
+fn new_user_fails_without_deposit() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(DatVerify::new_user(Origin::signed(account(11))), Error::<Test>::CannotReserve);
+	});
+}
+
+#[test]
+fn publish_reserves_nothing_when_escrow_fails() {
+	new_test_ext().execute_with(|| {
+		register(1, &[], 0);
+		let reserved = Balances::reserved_balance(account(1));
+		let payment = PlanPayment { asset: HOSTING_ASSET, per_proof: 1, escrow: ENDOWMENT + 1 };
+		assert!(DatVerify::publish_feed_and_plan(
+			Origin::signed(account(1)), merkle_root(1, 4), vec![(0, 3)], 0, Some(payment)
+		).is_err());
+		assert_eq!(Balances::reserved_balance(account(1)), reserved);
+		assert_eq!(<GetNextFeedID<Test>>::get(), 0);
+		assert_eq!(<GetNextPlanID<Test>>::get(), 0);
+		assert!(<GetDepositByItem<Test>>::get(Deposited::Feed(0)).is_none());
+	});
+}
+
+#[test]
+fn publish_fails_before_escrow_without_deposit() {
+	new_test_ext().execute_with(|| {
+		register(1, &[], 0);
+		let free = Balances::free_balance(account(1));
+		assert_ok!(Balances::reserve(&account(1), free - 1));
+		let payment = PlanPayment { asset: HOSTING_ASSET, per_proof: 1, escrow: 10 };
+		assert_noop!(
+			DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(1, 4), vec![(0, 3)], 0, Some(payment)),
+			Error::<Test>::CannotReserve
+		);
+		assert_eq!(GenericAsset::reserved_balance(&HOSTING_ASSET, &account(1)), 0);
+	});
+}
+
+// encoder 1 and hosters 2 and 3, publisher 1 publishes a plan whose contract 0 starts hosting,
+// returns the account of its hoster and of the other hoster
+fn hosted_plan() -> (AccountId, AccountId) {
+	register(1, &[Role::Encoder], 0);
+	register(2, &[Role::Hoster], 0);
+	register(3, &[Role::Hoster], 0);
+	publish(9, 1, 4);
+	let hoster = DatVerify::address_of(&<GetContractByID<Test>>::get(0).unwrap().hoster);
+	assert_ok!(DatVerify::hosting_starts(Origin::signed(hoster.clone()), 0));
+	let other = if hoster == account(2) { account(3) } else { account(2) };
+	(hoster, other)
+}
+
+#[test]
+fn empty_submissions_fail() {
+	new_test_ext().execute_with(|| {
+		let (hoster, _) = hosted_plan();
+		register(4, &[Role::Attestor], 0);
+		assert_ok!(DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0));
+		assert_noop!(
+			DatVerify::submit_proof_of_storage(Origin::signed(hoster), 0, vec![]),
+			Error::<Test>::ProofCountMismatch
+		);
+		assert_ok!(DatVerify::request_attestation(Origin::signed(account(1)), 0));
+		assert_noop!(
+			DatVerify::submit_attestation_report(Origin::signed(account(4)), 0, vec![]),
+			Error::<Test>::ReportCountMismatch
+		);
+	});
+}
+
+// challenges contract 0 and answers with a proof for every chunk
+fn pass_challenge(hoster: AccountId) {
+	assert_ok!(DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0));
+	let challenge_id = <GetNextChallengeID<Test>>::get() - 1;
+	let chunks = <GetChallengeByID<Test>>::get(challenge_id).unwrap().chunks;
+	let proofs = chunks.iter().map(|_| Public::from_raw([0; 32])).collect();
+	assert_ok!(DatVerify::submit_proof_of_storage(Origin::signed(hoster), challenge_id, proofs));
+}
+
+// encoder 1 and hoster 2, publisher 1 pays 30 per proof out of an escrow of 50
+fn paid_plan() {
+	register(1, &[Role::Encoder], 0);
+	register(2, &[Role::Hoster], 0);
+	let payment = PlanPayment { asset: HOSTING_ASSET, per_proof: 30, escrow: 50 };
+	assert_ok!(DatVerify::publish_feed_and_plan(
+		Origin::signed(account(1)), merkle_root(9, 4), vec![(0, 3)], 0, Some(payment)
+	));
+	assert_ok!(DatVerify::hosting_starts(Origin::signed(account(2)), 0));
+}
+
+fn escrow_of_plan(plan_id: u32) -> Balance {
+	<GetPlanByID<Test>>::get(plan_id).unwrap().payment.unwrap().escrow
+}
+
+#[test]
+fn passed_proofs_pay_hoster_from_escrow() {
+	new_test_ext().execute_with(|| {
+		paid_plan();
+		assert_eq!(GenericAsset::reserved_balance(&HOSTING_ASSET, &account(1)), 50);
+		pass_challenge(account(2));
+		assert_eq!(GenericAsset::free_balance(&HOSTING_ASSET, &account(2)), ENDOWMENT + 30);
+		assert_eq!(GenericAsset::reserved_balance(&HOSTING_ASSET, &account(1)), 20);
+		assert_eq!(escrow_of_plan(0), 20);
+	});
+}
+
+#[test]
+fn payouts_stop_once_escrow_is_empty() {
+	new_test_ext().execute_with(|| {
+		paid_plan();
+		pass_challenge(account(2));
+		// only the rest of the escrow is paid
+		pass_challenge(account(2));
+		assert_eq!(GenericAsset::free_balance(&HOSTING_ASSET, &account(2)), ENDOWMENT + 50);
+		assert_eq!(escrow_of_plan(0), 0);
+		pass_challenge(account(2));
+		assert_eq!(GenericAsset::free_balance(&HOSTING_ASSET, &account(2)), ENDOWMENT + 50);
+		assert_eq!(GenericAsset::reserved_balance(&HOSTING_ASSET, &account(1)), 0);
+		// topping the escrow up resumes them
+		assert_ok!(DatVerify::fund_plan(Origin::signed(account(1)), 0, 10));
+		pass_challenge(account(2));
+		assert_eq!(GenericAsset::free_balance(&HOSTING_ASSET, &account(2)), ENDOWMENT + 60);
+		assert_eq!(escrow_of_plan(0), 0);
+	});
+}
+
+#[test]
+fn passed_submissions_are_free() {
+	new_test_ext().execute_with(|| {
+		let (hoster, _) = hosted_plan();
+		register(4, &[Role::Attestor], 0);
+		assert_ok!(DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0));
+		let proofs = <GetChallengeByID<Test>>::get(0).unwrap().chunks.iter()
+			.map(|_| Public::from_raw([0; 32]))
+			.collect();
+		let info = DatVerify::submit_proof_of_storage(Origin::signed(hoster), 0, proofs).unwrap();
+		assert_eq!(info.pays_fee, Pays::No);
+
+		let report = |latency| vec![Report { location: Some(0), latency: latency }];
+		assert_ok!(DatVerify::request_attestation(Origin::signed(account(1)), 0));
+		let info = DatVerify::submit_attestation_report(Origin::signed(account(4)), 0, report(Some(10))).unwrap();
+		assert_eq!(info.pays_fee, Pays::No);
+		assert_ok!(DatVerify::request_attestation(Origin::signed(account(1)), 0));
+		let info = DatVerify::submit_attestation_report(Origin::signed(account(4)), 1, report(None)).unwrap();
+		assert_eq!(info.pays_fee, Pays::Yes);
+	});
+}
+
+fn status_of(contract_id: u32) -> ContractStatus {
+	<GetContractByID<Test>>::get(contract_id).unwrap().status
+}
+
+#[test]
+fn ending_replacement_retries_handover() {
+	new_test_ext().execute_with(|| {
+		let (hoster, other) = hosted_plan();
+		assert_ok!(DatVerify::unregister_hoster(Origin::signed(hoster)));
+		assert_eq!(status_of(0), ContractStatus::Retiring);
+		assert_eq!(<GetHandoverByContract<Test>>::get(1), Some(0));
+
+		assert_ok!(DatVerify::force_end_contract(Origin::root(), 1));
+		assert_eq!(status_of(1), ContractStatus::Ended);
+		assert_eq!(status_of(0), ContractStatus::Hosting);
+		assert_eq!(<GetHandoverByContract<Test>>::get(1), None);
+
+		run_to_block(2);
+		assert_eq!(status_of(0), ContractStatus::Retiring);
+		assert_eq!(<GetHandoverByContract<Test>>::get(2), Some(0));
+		assert_eq!(DatVerify::address_of(&<GetContractByID<Test>>::get(2).unwrap().hoster), other);
+	});
+}
+
+#[test]
+fn force_end_rejects_ended_contract() {
+	new_test_ext().execute_with(|| {
+		hosted_plan();
+		assert_ok!(DatVerify::force_end_contract(Origin::root(), 0));
+		assert_noop!(DatVerify::force_end_contract(Origin::root(), 0), Error::<Test>::ContractEnded);
+	});
+}
+
+#[test]
+fn random_of_vec_picks_distinct_members() {
+	new_test_ext().execute_with(|| {
+		for count in 0..=5 {
+			let mut picked = DatVerify::get_random_of_vec(&[], vec![1u32, 2, 3, 4, 5], count);
+			assert_eq!(picked.len(), count as usize);
+			picked.sort();
+			picked.dedup();
+			assert_eq!(picked.len(), count as usize);
+		}
+		assert_eq!(DatVerify::get_random_of_vec(&[], vec![1u32, 2], 3), vec![1, 2]);
+	});
+}
+
+#[test]
+fn plan_regions_need_as_many_hosters() {
+	new_test_ext().execute_with(|| {
+		register(1, &[], 0);
+		assert_noop!(
+			DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(1, 4), vec![(0, 3)], 2, None),
+			Error::<Test>::TooManyRegions
+		);
+	});
+}
+
+#[test]
+fn plan_hosters_cover_distinct_regions() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(DatVerify::set_parameters(Origin::root(), PalletParameters {
+			hosters_per_plan: 2,
+			..DatVerify::parameters()
+		}));
+		register(1, &[Role::Encoder], 0);
+		register(2, &[Role::Hoster], 1);
+		register(3, &[Role::Hoster], 1);
+		register(4, &[Role::Hoster], 2);
+		assert_ok!(DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(9, 4), vec![(0, 3)], 2, None));
+		assert_eq!(DatVerify::hosters_of_plan(&0).len(), 2);
+		assert_eq!(DatVerify::regions_of_plan(&0), vec![1, 2].into_iter().collect());
+	});
+}
+
+#[test]
+fn parameters_need_challenge_delay_and_attestors() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			DatVerify::set_parameters(Origin::root(), PalletParameters {
+				challenge_delay: 0,
+				..DatVerify::parameters()
+			}),
+			Error::<Test>::InvalidParameters
+		);
+		assert_noop!(
+			DatVerify::set_parameters(Origin::root(), PalletParameters {
+				attestors_per_challenge: 0,
+				..DatVerify::parameters()
+			}),
+			Error::<Test>::InvalidParameters
+		);
+		assert_ok!(DatVerify::set_parameters(Origin::root(), PalletParameters {
+			challenge_delay: 1,
+			..DatVerify::parameters()
+		}));
+	});
+}
+
+#[test]
+fn attestation_request_samples_distinct_attestors() {
+	new_test_ext().execute_with(|| {
+		hosted_plan();
+		for seed in 4..=7 {
+			register(seed, &[Role::Attestor], 0);
+		}
+		assert_ok!(DatVerify::set_parameters(Origin::root(), PalletParameters {
+			attestors_per_challenge: 3,
+			..DatVerify::parameters()
+		}));
+		assert_ok!(DatVerify::request_attestation(Origin::signed(account(1)), 0));
+		let attestors : BTreeSet<u32> = <GetAttestationByID<Test>>::iter()
+			.map(|(_, attestation)| attestation.attestor)
+			.collect();
+		assert_eq!(<GetAttestationByID<Test>>::iter().count(), 3);
+		assert_eq!(attestors.len(), 3);
+	});
+}
+
+// gives the user the hoster role in the region without matching it with any plan
+fn add_idle_hoster(seed: u8, region: Region) -> u32 {
+	let hoster = register(seed, &[], region);
+	<Roles<Test>>::insert(Role::Hoster, hoster, RoleValue::Some(0));
+	<GetRegionByHoster<Test>>::insert(hoster, HosterRegion { region, ..Default::default() });
+	hoster
+}
+
+fn contract_of_hoster(hoster: AccountId) -> u32 {
+	let hoster_id = <GetIDByUser<Test>>::get(hoster).unwrap();
+	<ContractsByHoster<Test>>::iter_prefix(hoster_id).next().unwrap().0
+}
+
+#[test]
+fn handover_skips_hosters_of_the_plan() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(DatVerify::set_parameters(Origin::root(), PalletParameters {
+			hosters_per_plan: 2,
+			..DatVerify::parameters()
+		}));
+		register(1, &[Role::Encoder], 0);
+		register(2, &[Role::Hoster], 0);
+		register(3, &[Role::Hoster], 0);
+		publish(9, 1, 4);
+		let contract_id = contract_of_hoster(account(2));
+		// hoster 3 already holds a replica of the plan
+		assert_ok!(DatVerify::unregister_hoster(Origin::signed(account(2))));
+		assert_ne!(status_of(contract_id), ContractStatus::Retiring);
+		assert_eq!(<GetHandoverByContract<Test>>::iter().count(), 0);
+
+		let hoster = add_idle_hoster(4, 0);
+		run_to_block(2);
+		assert_eq!(status_of(contract_id), ContractStatus::Retiring);
+		let (replacement_id, _) = <GetHandoverByContract<Test>>::iter().next().unwrap();
+		assert_eq!(<GetContractByID<Test>>::get(replacement_id).unwrap().hoster, hoster);
+	});
+}
+
+#[test]
+fn handover_keeps_the_regions_of_the_plan() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(DatVerify::set_parameters(Origin::root(), PalletParameters {
+			hosters_per_plan: 2,
+			..DatVerify::parameters()
+		}));
+		register(1, &[Role::Encoder], 0);
+		register(2, &[Role::Hoster], 1);
+		register(3, &[Role::Hoster], 2);
+		assert_ok!(DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(9, 4), vec![(0, 3)], 2, None));
+		add_idle_hoster(4, 1);
+		let hoster = add_idle_hoster(5, 3);
+		let contract_id = contract_of_hoster(account(3));
+		// hoster 4 would leave the plan with region 1 only
+		assert_ok!(DatVerify::unregister_hoster(Origin::signed(account(3))));
+		assert_eq!(status_of(contract_id), ContractStatus::Retiring);
+		let (replacement_id, retiring_id) = <GetHandoverByContract<Test>>::iter().next().unwrap();
+		assert_eq!(retiring_id, contract_id);
+		assert_eq!(<GetContractByID<Test>>::get(replacement_id).unwrap().hoster, hoster);
+	});
+}
+
+#[test]
+fn attestation_request_stores_nothing_without_all_deposits() {
+	new_test_ext().execute_with(|| {
+		hosted_plan();
+		for seed in 4..=6 {
+			register(seed, &[Role::Attestor], 0);
+		}
+		assert_ok!(DatVerify::set_parameters(Origin::root(), PalletParameters {
+			attestors_per_challenge: 3,
+			..DatVerify::parameters()
+		}));
+		// enough for the deposit of one attestation, not of three
+		let free = Balances::free_balance(account(1));
+		assert_ok!(Balances::reserve(&account(1), free - 30));
+		assert_noop!(
+			DatVerify::request_attestation(Origin::signed(account(1)), 0),
+			Error::<Test>::CannotReserve
+		);
+	});
+}
+
+// every stored contract, plan and challenge is indexed under its own fields, and every index entry
+// points at a stored item with those fields
+fn assert_indexes_match() {
+	for (contract_id, contract) in <GetContractByID<Test>>::iter() {
+		assert!(<ContractsByHoster<Test>>::contains_key(contract.hoster, contract_id));
+		assert!(<ContractsByEncoder<Test>>::contains_key(contract.encoder, contract_id));
+		assert!(<ContractsByPlan<Test>>::contains_key(contract.plan, contract_id));
+	}
+	for (hoster_id, contract_id, _) in <ContractsByHoster<Test>>::iter() {
+		assert_eq!(<GetContractByID<Test>>::get(contract_id).map(|contract| contract.hoster), Some(hoster_id));
+	}
+	for (encoder_id, contract_id, _) in <ContractsByEncoder<Test>>::iter() {
+		assert_eq!(<GetContractByID<Test>>::get(contract_id).map(|contract| contract.encoder), Some(encoder_id));
+	}
+	for (plan_id, contract_id, _) in <ContractsByPlan<Test>>::iter() {
+		assert_eq!(<GetContractByID<Test>>::get(contract_id).map(|contract| contract.plan), Some(plan_id));
+	}
+	for (plan_id, plan) in <GetPlanByID<Test>>::iter() {
+		assert!(<PlansByFeed<Test>>::contains_key(plan.feed, plan_id));
+	}
+	for (feed_id, plan_id, _) in <PlansByFeed<Test>>::iter() {
+		assert_eq!(<GetPlanByID<Test>>::get(plan_id).map(|plan| plan.feed), Some(feed_id));
+	}
+	for (challenge_id, challenge) in <GetChallengeByID<Test>>::iter() {
+		assert!(<ChallengesByContract<Test>>::contains_key(challenge.contract, challenge_id));
+	}
+	for (contract_id, challenge_id, _) in <ChallengesByContract<Test>>::iter() {
+		assert_eq!(<GetChallengeByID<Test>>::get(challenge_id).map(|challenge| challenge.contract), Some(contract_id));
+	}
+}
+
+#[test]
+fn indexes_follow_items_through_their_lifecycle() {
+	new_test_ext().execute_with(|| {
+		let (hoster, _) = hosted_plan();
+		assert_indexes_match();
+
+		assert_ok!(DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0));
+		assert_eq!(<ChallengesByContract<Test>>::iter_prefix(0).count(), 1);
+		assert_indexes_match();
+
+		assert_ok!(DatVerify::unregister_hoster(Origin::signed(hoster)));
+		assert_eq!(<ContractsByPlan<Test>>::iter_prefix(0).count(), 2);
+		assert_indexes_match();
+
+		assert_ok!(DatVerify::force_end_contract(Origin::root(), 1));
+		assert_indexes_match();
+
+		publish(8, 1, 2);
+		assert_indexes_match();
+		assert_ok!(DatVerify::unpublish_plan(Origin::signed(account(1)), 1));
+		assert!(<PlansByFeed<Test>>::iter_prefix(1).next().is_none());
+		assert_indexes_match();
+
+		assert_ok!(DatVerify::delete_feed(Origin::root(), 0));
+		assert!(<GetPlanByID<Test>>::get(0).is_none());
+		assert_indexes_match();
+
+		// past the challenge deadline and the retention of everything resolved or ended
+		run_to_block(40);
+		assert_indexes_match();
+		assert_eq!(<GetContractByID<Test>>::iter().count(), 0);
+		assert_eq!(<GetChallengeByID<Test>>::iter().count(), 0);
+		assert_eq!(<ContractsByPlan<Test>>::iter().count(), 0);
+		assert_eq!(<ChallengesByContract<Test>>::iter().count(), 0);
+	});
+}
+
+fn put_raw<V: Encode>(key: Vec<u8>, value: V) {
+	frame_support::storage::unhashed::put(&key, &value);
+}
+
+#[test]
+fn migrates_unversioned_storage() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::put(Releases::V1_0_0);
+		put_raw(<GetPlanByID<Test>>::hashed_key_for(0), migration::v1::Plan::<Test> {
+			id: 0, feed: 0, publisher: 0, ranges: vec![(0, 3)]
+		});
+		put_raw(<GetContractByID<Test>>::hashed_key_for(0), migration::v1::Contract::<Test> {
+			id: 0, plan: 0, ranges: vec![(0, 3)], encoder: 1, hoster: 2
+		});
+		put_raw(<GetChallengeByID<Test>>::hashed_key_for(0), migration::v1::Challenge::<Test> {
+			id: 0, contract: 0, chunks: vec![2]
+		});
+		put_raw(<GetAttestationByID<Test>>::hashed_key_for(0), migration::v1::Attestation::<Test> {
+			id: 0, attestor: 3, contract: 0
+		});
+
+		migration::migrate::<Test>();
+
+		assert_eq!(StorageVersion::get(), migration::CURRENT_RELEASE);
+		assert_eq!(<GetPlanByID<Test>>::get(0), Some(Plan {
+			id: 0, feed: 0, publisher: 0, ranges: vec![(0, 3)], min_regions: 0, payment: None
+		}));
+		assert!(<PlansByFeed<Test>>::contains_key(0, 0));
+
+		assert_eq!(<GetContractByID<Test>>::get(0), Some(Contract {
+			id: 0, plan: 0, ranges: vec![(0, 3)], encoder: 1, hoster: 2, status: ContractStatus::Hosting
+		}));
+		assert!(<ContractsByHoster<Test>>::contains_key(2, 0));
+		assert!(<ContractsByEncoder<Test>>::contains_key(1, 0));
+		assert!(<ContractsByPlan<Test>>::contains_key(0, 0));
+		assert_eq!(DatVerify::hosting_contracts(), vec![0]);
+
+		let deadline = 1 + ChallengeDelay::get();
+		assert_eq!(<GetChallengeByID<Test>>::get(0), Some(Challenge {
+			id: 0, contract: 0, chunks: vec![2], deadline: deadline, status: ChallengeStatus::Open
+		}));
+		assert!(<ChallengesByContract<Test>>::contains_key(0, 0));
+		assert!(<ChallengesByDeadline<Test>>::contains_key(deadline, 0));
+
+		let attestation = <GetAttestationByID<Test>>::get(0).unwrap();
+		assert_eq!((attestation.attestor, attestation.contract), (3, 0));
+		assert_eq!(attestation.status, ChallengeStatus::Open);
+		assert!(!attestation.chunks.is_empty());
+		assert!(attestation.chunks.iter().all(|chunk| *chunk <= 3));
+	});
+}
+
+#[test]
+fn migrates_plans_without_payment() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::put(Releases::V2_0_0);
+		put_raw(<GetPlanByID<Test>>::hashed_key_for(4), migration::v2::Plan::<Test> {
+			id: 4, feed: 1, publisher: 2, ranges: vec![(1, 2), (5, 9)], min_regions: 3
+		});
+
+		migration::migrate::<Test>();
+
+		assert_eq!(StorageVersion::get(), Releases::V3_0_0);
+		assert_eq!(<GetPlanByID<Test>>::get(4), Some(Plan {
+			id: 4, feed: 1, publisher: 2, ranges: vec![(1, 2), (5, 9)], min_regions: 3, payment: None
+		}));
+	});
+}
+
+#[test]
+fn migration_keeps_current_storage() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(StorageVersion::get(), migration::CURRENT_RELEASE);
+		register(1, &[], 0);
+		publish(1, 1, 4);
+		let plan = <GetPlanByID<Test>>::get(0);
+		migration::migrate::<Test>();
+		assert_eq!(<GetPlanByID<Test>>::get(0), plan);
+	});
+}
+
+#[test]
+fn exhausted_user_ids_fail() {
+	new_test_ext().execute_with(|| {
+		<GetNextUserID<Test>>::put(u32::max_value());
+		assert_noop!(DatVerify::new_user(Origin::signed(account(1))), Error::<Test>::IdsExhausted);
+	});
+}
+
+#[test]
+fn exhausted_feed_and_plan_ids_fail() {
+	new_test_ext().execute_with(|| {
+		register(1, &[], 0);
+		<GetNextFeedID<Test>>::put(u32::max_value());
+		assert_noop!(
+			DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(1, 4), vec![(0, 3)], 0, None),
+			Error::<Test>::IdsExhausted
+		);
+		<GetNextFeedID<Test>>::put(0);
+		<GetNextPlanID<Test>>::put(u32::max_value());
+		assert_noop!(
+			DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(1, 4), vec![(0, 3)], 0, None),
+			Error::<Test>::IdsExhausted
+		);
+	});
+}
+
+#[test]
+fn exhausted_contract_ids_fail() {
+	new_test_ext().execute_with(|| {
+		register(1, &[Role::Encoder], 0);
+		register(2, &[Role::Hoster], 0);
+		<GetNextContractID<Test>>::put(u32::max_value());
+		assert_noop!(
+			DatVerify::publish_feed_and_plan(Origin::signed(account(1)), merkle_root(1, 4), vec![(0, 3)], 0, None),
+			Error::<Test>::IdsExhausted
+		);
+		assert_eq!(<GetNextContractID<Test>>::get(), u32::max_value());
+		assert!(<GetContractByID<Test>>::iter().next().is_none());
+	});
+}
+
+#[test]
+fn exhausted_challenge_ids_fail() {
+	new_test_ext().execute_with(|| {
+		hosted_plan();
+		<GetNextChallengeID<Test>>::put(u32::max_value());
+		assert_noop!(
+			DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0),
+			Error::<Test>::IdsExhausted
+		);
+	});
+}
+
+#[test]
+fn challenge_request_fails_before_writes_without_deposit() {
+	new_test_ext().execute_with(|| {
+		hosted_plan();
+		let free = Balances::free_balance(account(1));
+		assert_ok!(Balances::reserve(&account(1), free - 1));
+		assert_noop!(
+			DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0),
+			Error::<Test>::CannotReserve
+		);
+	});
+}
+
+#[test]
+fn hoster_registration_fails_before_bond() {
+	new_test_ext().execute_with(|| {
+		register(1, &[Role::Encoder], 0);
+		register(2, &[], 0);
+		publish(9, 1, 4);
+		<GetNextContractID<Test>>::put(u32::max_value());
+		assert_noop!(
+			DatVerify::register_hoster(Origin::signed(account(2)), noise_key(2), 0),
+			Error::<Test>::IdsExhausted
+		);
+		<GetNextContractID<Test>>::put(0);
+		let free = Balances::free_balance(account(2));
+		assert_ok!(Balances::reserve(&account(2), free - HosterBond::get() + 1));
+		assert_noop!(
+			DatVerify::register_hoster(Origin::signed(account(2)), noise_key(2), 0),
+			Error::<Test>::CannotReserve
+		);
+	});
+}
+
+#[test]
+fn challenges_need_a_hosted_contract() {
+	new_test_ext().execute_with(|| {
+		register(1, &[Role::Encoder], 0);
+		register(2, &[Role::Hoster], 0);
+		publish(9, 1, 4);
+		assert_noop!(
+			DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0),
+			Error::<Test>::ContractNotHosting
+		);
+		assert_ok!(DatVerify::hosting_starts(Origin::signed(account(2)), 0));
+		assert_ok!(DatVerify::force_end_contract(Origin::root(), 0));
+		assert_noop!(
+			DatVerify::request_proof_of_storage_challenge(Origin::signed(account(1)), 0),
+			Error::<Test>::ContractNotHosting
+		);
+	});
+}
+
+#[test]
+fn audits_challenge_hosted_contracts_for_free() {
+	new_test_ext().execute_with(|| {
+		hosted_plan();
+		// every contract is audited
+		assert_ok!(DatVerify::set_parameters(Origin::root(), PalletParameters {
+			default_audits_per_period: AuditPeriod::get() as u32,
+			..DatVerify::parameters()
+		}));
+		let reserved = Balances::reserved_balance(account(1));
+		let weight = DatVerify::issue_audits();
+		assert_eq!(<ChallengesByContract<Test>>::iter_prefix(0).count(), 1);
+		assert_eq!(Balances::reserved_balance(account(1)), reserved);
+		assert!(<GetDepositByItem<Test>>::get(Deposited::Challenge(0)).is_none());
+		// the hosted contract list and one audit
+		assert!(weight >= DbWeight::get().reads_writes(2 + 7, 4));
+
+		// the plan's own rate overrides the default
+		assert_ok!(DatVerify::set_plan_audit_rate(Origin::signed(account(1)), 0, Some(0)));
+		DatVerify::issue_audits();
+		assert_eq!(<ChallengesByContract<Test>>::iter_prefix(0).count(), 1);
+	});
+}
+
+#[test]
+fn exhausted_attestation_ids_fail() {
+	new_test_ext().execute_with(|| {
+		hosted_plan();
+		register(4, &[Role::Attestor], 0);
+		<GetNextAttestationID<Test>>::put(u32::max_value());
+		assert_noop!(
+			DatVerify::request_attestation(Origin::signed(account(1)), 0),
+			Error::<Test>::IdsExhausted
+		);
+	});
+}
+
+#[test]
+fn region_reports_follow_observed_latency() {
+	new_test_ext().execute_with(|| {
+		let hoster = register(2, &[Role::Hoster], 1);
+		let region = || <GetRegionByHoster<Test>>::get(hoster);
+		// slow from another region: no evidence either way
+		DatVerify::record_region_report(hoster, 2, NEARBY_LATENCY + 1);
+		assert_eq!((region().confirmations, region().contradictions), (0, 0));
+		// slow from the claimed region
+		DatVerify::record_region_report(hoster, 1, NEARBY_LATENCY + 1);
+		assert_eq!((region().confirmations, region().contradictions), (0, 1));
+		// fast from another region
+		DatVerify::record_region_report(hoster, 2, NEARBY_LATENCY);
+		assert_eq!((region().confirmations, region().contradictions), (0, 2));
+		// fast from the claimed region
+		DatVerify::record_region_report(hoster, 1, 0);
+		assert_eq!((region().confirmations, region().contradictions), (1, 2));
+	});
+}
+
+#[test]
+fn attestor_fetches_chunks_from_hoster_endpoint() {
+	use sp_core::{
+		offchain::{testing, OffchainExt, TransactionPoolExt},
+		testing::KeyStore,
+		traits::KeystoreExt,
+	};
+
+	let mut ext = new_test_ext();
+	let (offchain, offchain_state) = testing::TestOffchainExt::new();
+	let (pool, pool_state) = testing::TestTransactionPoolExt::new();
+	let keystore = KeyStore::new();
+	let public = keystore.write().sr25519_generate_new(KEY_TYPE, None).unwrap();
+	ext.register_extension(OffchainExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+	ext.register_extension(KeystoreExt(keystore));
+
+	ext.execute_with(|| {
+		hosted_plan();
+		let hoster_id = <GetContractByID<Test>>::get(0).unwrap().hoster;
+		let attestor = AccountId::from(public.0);
+		let _ = Balances::deposit_creating(&attestor, ENDOWMENT);
+		assert_ok!(DatVerify::new_user(Origin::signed(attestor.clone())));
+		assert_ok!(DatVerify::register_attestor(Origin::signed(attestor)));
+		assert_ok!(DatVerify::request_attestation(Origin::signed(account(1)), 0));
+		assert_eq!(<GetAttestationByID<Test>>::get(0).unwrap().chunks, vec![0]);
+
+		sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, b"datdot::hoster_endpoint", b"http://node.test");
+		sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, &DatVerify::hoster_endpoint_key(&hoster_id), b"http://hoster.test");
+		sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, b"datdot::attestor_region", &3u8.encode());
+		offchain_state.write().expect_request(0, testing::PendingRequest {
+			method: "GET".into(),
+			uri: format!("http://hoster.test/{}/0", "09".repeat(32)),
+			response: Some(b"chunk".to_vec()),
+			sent: true,
+			..Default::default()
+		});
+
+		DatVerify::attest_retrievability();
+
+		let tx = pool_state.write().transactions.pop().unwrap();
+		assert!(pool_state.read().transactions.is_empty());
+		let tx = Extrinsic::decode(&mut &*tx).unwrap();
+		assert!(tx.signature.is_some());
+		// the report carries the attestor's own region
+		assert_eq!(tx.call, Call::submit_attestation_report(0, vec![Report { location: Some(3), latency: Some(0) }]));
+	});
+}
+
+#[test]
+fn handover_retries_are_capped_per_block() {
+	new_test_ext().execute_with(|| {
+		register(1, &[Role::Encoder], 0);
+		register(2, &[Role::Hoster], 0);
+		for seed in 7..10 {
+			publish(seed, 1, 4);
+		}
+		// no other hoster to hand over to yet
+		assert_ok!(DatVerify::unregister_hoster(Origin::signed(account(2))));
+		let retiring = || <GetContractByID<Test>>::iter()
+			.filter(|(_, contract)| contract.status == ContractStatus::Retiring)
+			.count() as u32;
+		assert_eq!(retiring(), 0);
+
+		// a hoster joining without being matched on registration
+		let hoster = register(3, &[], 0);
+		<Roles<Test>>::insert(Role::Hoster, hoster, RoleValue::Some(0));
+		let weight = DatVerify::process_hoster_exits(1);
+		assert_eq!(retiring(), MaxHandoversPerBlock::get());
+		// exits, hosters and encoders, then per retry at least the scan of both hosters
+		assert!(weight >= 3 + 2 * (2 * 3));
+		run_to_block(2);
+		assert_eq!(retiring(), 3);
+	});
+}
+
+#[test]
+fn exported_items_stay_serializable() {
+	fn assert_serde<Item: Serialize + serde::de::DeserializeOwned>() {}
+	assert_serde::<Plan<Test>>();
+	assert_serde::<StateDump<Test>>();
+}
@@ -1,10 +1,11 @@
 //! Helper module to build a genesis configuration for the weight-fee-runtime
 
 use super::{
-	AccountId, BalancesConfig, GenericAssetConfig, GenesisConfig, Signature, SudoConfig,
+	dat_verify::Role, AccountId, AuraConfig, AuraId, BalancesConfig, DatVerifyConfig,
+	GenericAssetConfig, GenesisConfig, GrandpaConfig, GrandpaId, Signature, SudoConfig,
 	SystemConfig, WASM_BINARY,
 };
-use sp_core::{sr25519, Pair};
+use sp_core::{ed25519, sr25519, Pair};
 use sp_runtime::traits::{IdentifyAccount, Verify};
 
 /// Helper function to generate a crypto pair from seed
@@ -24,8 +25,40 @@ where
 	AccountPublic::from(get_from_seed::<TPair>(seed)).into_account()
 }
 
+/// Helper function to generate the Aura and GRANDPA authority keys of a seed
+pub fn authority_keys_from_seed(seed: &str) -> (AuraId, GrandpaId) {
+	(
+		get_from_seed::<sr25519::Pair>(seed).into(),
+		get_from_seed::<ed25519::Pair>(seed).into(),
+	)
+}
+
+/// Generic asset endowed to every endowed account for paying hosting plans
+pub const TEST_HOSTING_ASSET: u32 = 2;
+
+/// A datdot user registered at genesis: (address, noise key, roles, hoster region)
+pub type DatdotUser = (AccountId, Option<ed25519::Public>, Vec<Role>, u8);
+
+/// Registers the dev account of each seed as encoder, hoster and attestor,
+/// with the ed25519 key of the same seed as noise key
+pub fn dev_datdot_users(seeds: &[&str]) -> Vec<DatdotUser> {
+	seeds
+		.iter()
+		.map(|seed| {
+			(
+				account_id_from_seed::<sr25519::Pair>(seed),
+				Some(get_from_seed::<ed25519::Pair>(seed)),
+				vec![Role::Encoder, Role::Hoster, Role::Attestor],
+				0,
+			)
+		})
+		.collect()
+}
+
 pub fn dev_genesis() -> GenesisConfig {
 	testnet_genesis(
+		// Initial Authorities
+		vec![authority_keys_from_seed("Alice")],
 		// Root Key
 		account_id_from_seed::<sr25519::Pair>("Alice"),
 		// Endowed Accounts
@@ -35,11 +68,18 @@ pub fn dev_genesis() -> GenesisConfig {
 			account_id_from_seed::<sr25519::Pair>("Alice//stash"),
 			account_id_from_seed::<sr25519::Pair>("Bob//stash"),
 		],
+		// Datdot Users
+		dev_datdot_users(&["Alice", "Bob"]),
 	)
 }
 
 /// Helper function to build a genesis configuration
-pub fn testnet_genesis(root_key: AccountId, endowed_accounts: Vec<AccountId>) -> GenesisConfig {
+pub fn testnet_genesis(
+	initial_authorities: Vec<(AuraId, GrandpaId)>,
+	root_key: AccountId,
+	endowed_accounts: Vec<AccountId>,
+	datdot_users: Vec<DatdotUser>,
+) -> GenesisConfig {
 	GenesisConfig {
 		system: Some(SystemConfig {
 			code: WASM_BINARY.to_vec(),
@@ -53,7 +93,7 @@ pub fn testnet_genesis(root_key: AccountId, endowed_accounts: Vec<AccountId>) ->
 				.collect(),
 		}),
 		generic_asset: Some(GenericAssetConfig {
-			assets: vec![13, 1],
+			assets: vec![13, 1, TEST_HOSTING_ASSET],
 			initial_balance: 10u128.pow(18 + 9), // 1 billion token with 18 decimals
 			endowed_accounts: endowed_accounts
 				.clone()
@@ -64,6 +104,17 @@ pub fn testnet_genesis(root_key: AccountId, endowed_accounts: Vec<AccountId>) ->
 			staking_asset_id: 1,
 			spending_asset_id: 1,
 		}),
+		aura: Some(AuraConfig {
+			authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
+		}),
+		grandpa: Some(GrandpaConfig {
+			authorities: initial_authorities.iter().map(|x| (x.1.clone(), 1)).collect(),
+		}),
 		sudo: Some(SudoConfig { key: root_key }),
+		dat_verify: Some(DatVerifyConfig {
+			users: datdot_users,
+			feeds: vec![],
+			state: None,
+		}),
 	}
 }
@@ -16,7 +16,8 @@ include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 pub mod genesis;
 
 use frame_support::{
-	traits::Get,
+	debug,
+	traits::{Get, KeyOwnerProofSystem},
 	weights::{
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
 		Weight,
@@ -26,28 +27,28 @@ use frame_support::{
 	},
 };
 use frame_system as system;
+use grandpa::fg_primitives;
+use grandpa::{AuthorityId as GrandpaId, AuthorityList as GrandpaAuthorityList};
 use smallvec::smallvec;
 use sp_api::impl_runtime_apis;
-use sp_core::{OpaqueMetadata, H256};
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{crypto::KeyTypeId, OpaqueMetadata, H256};
+use parity_scale_codec::Encode;
 use sp_runtime::traits::{
-	BlakeTwo256, Block as BlockT, IdentifyAccount, IdentityLookup, Saturating, Verify,
+	BlakeTwo256, Block as BlockT, IdentifyAccount, IdentityLookup, NumberFor, Saturating, Verify,
 };
 use sp_runtime::{
-	create_runtime_str, generic,
+	create_runtime_str, generic, impl_opaque_keys,
 	transaction_validity::{TransactionSource, TransactionValidity},
-	ApplyExtrinsicResult, MultiSignature,
+	ApplyExtrinsicResult, MultiSignature, SaturatedConversion,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
 use sp_version::NativeVersion;
 use sp_version::RuntimeVersion;
 
-// These structs are used in one of the commented-by-default implementations of
-// transaction_payment::Trait. Don't warn when they are unused.
-#[allow(unused_imports)]
-use generic_asset::{AssetCurrency, AssetIdProvider, SpendingAssetCurrency};
-#[allow(unused_imports)]
-use frame_support::weights::IdentityFee;
+#[cfg(feature = "asset-fees")]
+use generic_asset::SpendingAssetCurrency;
 
 // A few exports that help ease life for downstream crates.
 pub use balances::Call as BalancesCall;
@@ -56,6 +57,7 @@ pub use frame_support::{construct_runtime, parameter_types, traits::Randomness,
 pub use sp_runtime::BuildStorage;
 pub use sp_runtime::{Perbill, Permill};
 pub use timestamp::Call as TimestampCall;
+pub use dat_verify;
 
 /// An index to a block.
 pub type BlockNumber = u32;
@@ -98,6 +100,13 @@ pub mod opaque {
 	pub type Block = generic::Block<Header, UncheckedExtrinsic>;
 	/// Opaque block identifier type.
 	pub type BlockId = generic::BlockId<Block>;
+
+	impl_opaque_keys! {
+		pub struct SessionKeys {
+			pub aura: Aura,
+			pub grandpa: Grandpa,
+		}
+	}
 }
 
 /// This runtime version.
@@ -187,16 +196,39 @@ impl system::Trait for Runtime {
 }
 
 parameter_types! {
+	/// Aura slots last twice the minimum period.
 	pub const MinimumPeriod: u64 = 1000;
 }
 
 impl timestamp::Trait for Runtime {
 	/// A timestamp: milliseconds since the unix epoch.
 	type Moment = u64;
+	/// Not `Aura`: its check of at most one block per slot fails for instant sealed blocks.
 	type OnTimestampSet = ();
 	type MinimumPeriod = MinimumPeriod;
 }
 
+impl aura::Trait for Runtime {
+	type AuthorityId = AuraId;
+}
+
+impl grandpa::Trait for Runtime {
+	type Event = Event;
+	type Call = Call;
+
+	type KeyOwnerProofSystem = ();
+
+	type KeyOwnerProof =
+		<Self::KeyOwnerProofSystem as KeyOwnerProofSystem<(KeyTypeId, GrandpaId)>>::Proof;
+
+	type KeyOwnerIdentification = <Self::KeyOwnerProofSystem as KeyOwnerProofSystem<(
+		KeyTypeId,
+		GrandpaId,
+	)>>::IdentificationTuple;
+
+	type HandleEquivocation = ();
+}
+
 parameter_types! {
 	pub const ExistentialDeposit: u128 = 500;
 	pub const TransferFee: u128 = 0;
@@ -233,19 +265,95 @@ impl scheduler::Trait for Runtime {
 	type MaximumWeight = MaximumBlockWeight;
 }
 
+parameter_types! {
+	pub const TransactionByteFee: u128 = 1;
+}
+
+/// Convert from weight to balance via a simple coefficient multiplication. The associated type C
+/// encapsulates a constant in units of balance per weight.
+pub struct LinearWeightToFee<C>(sp_std::marker::PhantomData<C>);
+
+impl<C> WeightToFeePolynomial for LinearWeightToFee<C>
+where
+	C: Get<Balance>,
+{
+	type Balance = Balance;
+
+	fn polynomial() -> WeightToFeeCoefficients<Self::Balance> {
+		let coefficient = WeightToFeeCoefficient {
+			coeff_integer: C::get(),
+			coeff_frac: Perbill::zero(),
+			negative: false,
+			degree: 1,
+		};
+
+		smallvec!(coefficient)
+	}
+}
+
+parameter_types! {
+	/// Balance charged per unit of weight.
+	pub const FeeWeightRatio: u128 = 1_000;
+}
+
+/// Currency transaction fees are withdrawn from.
+#[cfg(not(feature = "asset-fees"))]
+pub type FeeCurrency = Balances;
+/// Currency transaction fees are withdrawn from: the generic asset set as `spending_asset_id`.
+#[cfg(feature = "asset-fees")]
+pub type FeeCurrency = SpendingAssetCurrency<Runtime>;
+
+impl transaction_payment::Trait for Runtime {
+	type Currency = FeeCurrency;
+	type OnTransactionPayment = ();
+	type TransactionByteFee = TransactionByteFee;
+	type WeightToFee = LinearWeightToFee<FeeWeightRatio>;
+	type FeeMultiplierUpdate = ();
+}
+
 parameter_types! {
 	pub const AttestorsPerChallenge: u32 = 1;
 	pub const MinEncodersPerHoster: u32 = 1;
-	pub const MinHostersPerArchive: u32 = 1;
+	pub const HostersPerPlan: u32 = 1;
 	pub const ChallengeDelay: u32 = 5;
+	pub const HosterBond: Balance = 1_000_000_000_000;
+	pub const HosterNoticePeriod: BlockNumber = 100;
+	pub const MaxHandoversPerBlock: u32 = 10;
+	pub const AuditPeriod: BlockNumber = 100;
+	pub const DefaultAuditsPerPeriod: u32 = 1;
+	/// Spend at most 5% of the block on issuing audit challenges.
+	pub const AuditWeightBudget: Weight = MaximumBlockWeight::get() / 20;
+	pub const ResolvedRetention: BlockNumber = 1_000;
+	pub const MaxPrunedPerBlock: u32 = 50;
+	pub const MaxRanges: u32 = 64;
+	pub const MaxRootChildren: u32 = 64;
+	pub const MaxChallengeChunks: u32 = 64;
+	pub const DepositPerByte: Balance = 10_000_000;
 }
 
 type DatDotIdType = u32;
 
 impl dat_verify::Trait for Runtime {
 	type Event = Event;
+	type AuthorityId = dat_verify::crypto::DatdotAuthId;
 	type Hash = Hash;
 	type Randomness = RandomnessCollectiveFlip;
+	type Currency = Balances;
+	type HosterBond = HosterBond;
+	type HosterNoticePeriod = HosterNoticePeriod;
+	type MaxHandoversPerBlock = MaxHandoversPerBlock;
+	type ChallengeDelay = ChallengeDelay;
+	type AuditPeriod = AuditPeriod;
+	type DefaultAuditsPerPeriod = DefaultAuditsPerPeriod;
+	type AttestorsPerChallenge = AttestorsPerChallenge;
+	type HostersPerPlan = HostersPerPlan;
+	type AuditWeightBudget = AuditWeightBudget;
+	type ResolvedRetention = ResolvedRetention;
+	type MaxPrunedPerBlock = MaxPrunedPerBlock;
+	type MaxRanges = MaxRanges;
+	type MaxRootChildren = MaxRootChildren;
+	type MaxChallengeChunks = MaxChallengeChunks;
+	type DepositPerByte = DepositPerByte;
 	type FeedId = DatDotIdType;
 	type UserId = DatDotIdType;
 	type ContractId = DatDotIdType;
@@ -255,6 +363,52 @@ impl dat_verify::Trait for Runtime {
 
 }
 
+impl<LocalCall> system::offchain::CreateSignedTransaction<LocalCall> for Runtime
+where
+	Call: From<LocalCall>,
+{
+	fn create_transaction<C: system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: Call,
+		public: <Signature as Verify>::Signer,
+		account: AccountId,
+		index: Index,
+	) -> Option<(Call, <UncheckedExtrinsic as sp_runtime::traits::Extrinsic>::SignaturePayload)> {
+		let period = BlockHashCount::get() as u64;
+		let current_block = System::block_number()
+			.saturated_into::<u64>()
+			.saturating_sub(1);
+		let extra: SignedExtra = (
+			system::CheckTxVersion::<Runtime>::new(),
+			system::CheckGenesis::<Runtime>::new(),
+			system::CheckEra::<Runtime>::from(generic::Era::mortal(period, current_block)),
+			system::CheckNonce::<Runtime>::from(index),
+			system::CheckWeight::<Runtime>::new(),
+			transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+		);
+		let raw_payload = SignedPayload::new(call, extra)
+			.map_err(|e| {
+				debug::native::warn!("Unable to create signed payload: {:?}", e);
+			})
+			.ok()?;
+		let signature = raw_payload.using_encoded(|payload| C::sign(payload, public))?;
+		let (call, extra, _) = raw_payload.deconstruct();
+		Some((call, (account, signature, extra)))
+	}
+}
+
+impl system::offchain::SigningTypes for Runtime {
+	type Public = <Signature as Verify>::Signer;
+	type Signature = Signature;
+}
+
+impl<C> system::offchain::SendTransactionTypes<C> for Runtime
+where
+	Call: From<C>,
+{
+	type OverarchingCall = Call;
+	type Extrinsic = UncheckedExtrinsic;
+}
+
 construct_runtime!(
 	pub enum Runtime where
 		Block = Block,
@@ -263,12 +417,15 @@ construct_runtime!(
 	{
 		System: system::{Module, Call, Storage, Config, Event<T>},
 		Timestamp: timestamp::{Module, Call, Storage, Inherent},
+		Aura: aura::{Module, Config<T>, Inherent(Timestamp)},
+		Grandpa: grandpa::{Module, Call, Storage, Config, Event},
 		Balances: balances::{Module, Call, Storage, Config<T>, Event<T>},
 		GenericAsset: generic_asset::{Module, Call, Storage, Config<T>, Event<T>},
 		RandomnessCollectiveFlip: randomness_collective_flip::{Module, Call, Storage},
 		Sudo: sudo::{Module, Call, Config<T>, Storage, Event<T>},
 		Scheduler: scheduler::{Module, Call, Storage, Event<T>},
-		DatVerify: dat_verify::{Module, Call, Storage, Event<T>},
+		TransactionPayment: transaction_payment::{Module, Storage},
+		DatVerify: dat_verify::{Module, Call, Storage, Event<T>, Config},
 	}
 );
 
@@ -289,9 +446,12 @@ pub type SignedExtra = (
 	system::CheckEra<Runtime>,
 	system::CheckNonce<Runtime>,
 	system::CheckWeight<Runtime>,
+	transaction_payment::ChargeTransactionPayment<Runtime>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
+/// The payload being signed in transactions.
+pub type SignedPayload = generic::SignedPayload<Call, SignedExtra>;
 /// Extrinsic type that has already been checked.
 pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Call, SignedExtra>;
 /// Executive: handles dispatch to the various pallets.
@@ -359,15 +519,101 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl transaction_payment_rpc_runtime_api::TransactionPaymentApi<
+		Block,
+		Balance,
+		UncheckedExtrinsic,
+	> for Runtime {
+		fn query_info(
+			uxt: UncheckedExtrinsic,
+			len: u32,
+		) -> transaction_payment_rpc_runtime_api::RuntimeDispatchInfo<Balance> {
+			TransactionPayment::query_info(uxt, len)
+		}
+	}
+
+	impl dat_verify_runtime_api::DatdotApi<
+		Block,
+		AccountId,
+		DatDotIdType,
+		dat_verify::Role,
+		dat_verify::User<Runtime>,
+		dat_verify::Plan<Runtime>,
+		dat_verify::Contract<Runtime>,
+		dat_verify::Challenge<Runtime>,
+	> for Runtime {
+		fn contracts_by_user(user: AccountId) -> Vec<dat_verify::Contract<Runtime>> {
+			DatVerify::contracts_by_user(user)
+		}
+
+		fn open_challenges_by_hoster(hoster: AccountId) -> Vec<dat_verify::Challenge<Runtime>> {
+			DatVerify::open_challenges_by_hoster(hoster)
+		}
+
+		fn plans_by_feed(feed: DatDotIdType) -> Vec<dat_verify::Plan<Runtime>> {
+			DatVerify::plans_by_feed(feed)
+		}
+
+		fn active_providers(role: dat_verify::Role) -> Vec<dat_verify::User<Runtime>> {
+			DatVerify::active_providers(role)
+		}
+
+		fn protocol_stats() -> dat_verify_runtime_api::ProtocolStats {
+			let providers = |role| DatVerify::active_providers(role).len() as u32;
+			dat_verify_runtime_api::ProtocolStats {
+				encoders: providers(dat_verify::Role::Encoder),
+				hosters: providers(dat_verify::Role::Hoster),
+				attestors: providers(dat_verify::Role::Attestor),
+				active_contracts: DatVerify::hosting_contracts().len() as u32,
+				unmatched_plans: DatVerify::unmatched_plans().len() as u32,
+			}
+		}
+	}
+
+	impl sp_consensus_aura::AuraApi<Block, AuraId> for Runtime {
+		fn slot_duration() -> u64 {
+			Aura::slot_duration()
+		}
+
+		fn authorities() -> Vec<AuraId> {
+			Aura::authorities()
+		}
+	}
+
+	impl fg_primitives::GrandpaApi<Block> for Runtime {
+		fn grandpa_authorities() -> GrandpaAuthorityList {
+			Grandpa::grandpa_authorities()
+		}
+
+		fn submit_report_equivocation_extrinsic(
+			_equivocation_proof: fg_primitives::EquivocationProof<
+				<Block as BlockT>::Hash,
+				NumberFor<Block>,
+			>,
+			_key_owner_proof: fg_primitives::OpaqueKeyOwnershipProof,
+		) -> Option<()> {
+			None
+		}
+
+		fn generate_key_ownership_proof(
+			_set_id: fg_primitives::SetId,
+			_authority_id: GrandpaId,
+		) -> Option<fg_primitives::OpaqueKeyOwnershipProof> {
+			// the key owner proof type is a bottom type (`()` proof system), so there
+			// is nothing to prove ownership with
+			None
+		}
+	}
+
 	impl sp_session::SessionKeys<Block> for Runtime {
-		fn generate_session_keys(_seed: Option<Vec<u8>>) -> Vec<u8> {
-			Vec::new()
+		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
+			opaque::SessionKeys::generate(seed)
 		}
 
 		fn decode_session_keys(
-			_encoded: Vec<u8>,
-		) -> Option<Vec<(Vec<u8>, sp_core::crypto::KeyTypeId)>> {
-			None
+			encoded: Vec<u8>,
+		) -> Option<Vec<(Vec<u8>, KeyTypeId)>> {
+			opaque::SessionKeys::decode_into_raw_public_keys(&encoded)
 		}
 	}
 }
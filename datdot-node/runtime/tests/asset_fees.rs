@@ -0,0 +1,57 @@
+//! Fees of the `asset-fees` build, run with `cargo test -p datdot-runtime --features asset-fees`
+#![cfg(feature = "asset-fees")]
+
+use parity_scale_codec::Encode;
+use datdot_runtime::{
+	dat_verify, genesis::dev_genesis, Balances, BuildStorage, Call, Executive, GenericAsset,
+	Header, Runtime, SignedExtra, SignedPayload, TransactionPayment, UncheckedExtrinsic,
+};
+use sp_keyring::AccountKeyring;
+use sp_runtime::{generic::Era, traits::Header as _};
+
+/// `spending_asset_id` of the dev chain
+const SPENDING_ASSET: u32 = 1;
+
+fn signed(signer: AccountKeyring, call: Call, nonce: u32) -> UncheckedExtrinsic {
+	let extra: SignedExtra = (
+		frame_system::CheckTxVersion::<Runtime>::new(),
+		frame_system::CheckGenesis::<Runtime>::new(),
+		frame_system::CheckEra::<Runtime>::from(Era::Immortal),
+		frame_system::CheckNonce::<Runtime>::from(nonce),
+		frame_system::CheckWeight::<Runtime>::new(),
+		transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+	);
+	let payload = SignedPayload::new(call, extra).expect("extra of a dev chain is valid");
+	let signature = payload.using_encoded(|payload| signer.sign(payload));
+	let (call, extra, _) = payload.deconstruct();
+	UncheckedExtrinsic::new_signed(call, signer.to_account_id(), signature.into(), extra)
+}
+
+#[test]
+fn datdot_call_fee_is_paid_in_spending_asset() {
+	let storage = dev_genesis().build_storage().expect("dev genesis builds");
+	sp_io::TestExternalities::new(storage).execute_with(|| {
+		Executive::initialize_block(&Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		));
+		let bob = AccountKeyring::Bob.to_account_id();
+		let xt = signed(
+			AccountKeyring::Bob,
+			Call::DatVerify(dat_verify::Call::register_attestor()),
+			0,
+		);
+		let fee = TransactionPayment::query_info(xt.clone(), xt.encode().len() as u32).partial_fee;
+		assert!(fee > 0);
+		let asset_before = GenericAsset::free_balance(&SPENDING_ASSET, &bob);
+		let native_before = Balances::free_balance(&bob);
+
+		assert_eq!(Executive::apply_extrinsic(xt), Ok(Ok(())));
+
+		assert_eq!(GenericAsset::free_balance(&SPENDING_ASSET, &bob), asset_before - fee);
+		assert_eq!(Balances::free_balance(&bob), native_before);
+	});
+}
@@ -0,0 +1,35 @@
+// A stand-in hoster for testing the attestor offchain worker locally.
+// Serves chunks from disk the way the offchain worker expects to fetch them:
+//
+//   GET /<feed public key as hex>/<chunk index>
+//
+// with the chunk as body.
+//
+// usage: node datdot-node/scripts/stand-in-hoster.js <chunk dir> [port]
+// chunks are read from <chunk dir>/<feed public key as hex>/<chunk index>
+
+const fs = require('fs');
+const http = require('http');
+const path = require('path');
+
+const [dir = 'chunks', port = 8080] = process.argv.slice(2);
+
+const server = http.createServer((req, res) => {
+  const [feedKey, chunk] = req.url.split('/').filter(Boolean);
+  if (!/^[0-9a-f]{64}$/.test(feedKey || '') || !/^[0-9]+$/.test(chunk || '')) {
+    res.writeHead(400);
+    return res.end();
+  }
+  fs.readFile(path.join(dir, feedKey, chunk), (err, data) => {
+    if (err) {
+      console.log(`missing chunk ${chunk} of feed ${feedKey}`);
+      res.writeHead(404);
+      return res.end();
+    }
+    console.log(`serving chunk ${chunk} of feed ${feedKey}`);
+    res.writeHead(200);
+    res.end(data);
+  });
+});
+
+server.listen(port, () => console.log(`stand-in hoster listening on port ${port}`));